@@ -0,0 +1,23 @@
+use anyhow::Result;
+
+use crate::{
+    cache::{notifications::Notification, read, store::NotificationStore},
+    configuration::config::{Config, ReadPolicy},
+};
+
+/// Marks the notification as read locally right away, github is updated by the daemon once it is reachable.
+pub fn mark_as_read(store: &dyn NotificationStore, notification: &Notification) -> Result<()> {
+    read::queue_mark_as_read(store, &notification.id)
+}
+
+/// Opens the notification in the default browser and applies the configured read policy.
+pub fn open(store: &dyn NotificationStore, config: &Config, notification: &Notification) -> Result<()> {
+    open::that(&notification.url)
+        .map_err(|_| anyhow::anyhow!("Could not open url: {} in a browser", notification.url))?;
+
+    match config.additional_config.get_open_read_policy() {
+        ReadPolicy::Remote => mark_as_read(store, notification),
+        ReadPolicy::Local => read::mark_as_read(store, &notification.id),
+        ReadPolicy::Unread => Ok(()),
+    }
+}
@@ -0,0 +1,122 @@
+use std::{fmt, str::FromStr};
+
+use rusqlite::{Result, Row};
+
+use crate::cache::{notifications::timestamp, Cache};
+
+/// Remote changes to a thread that are queued locally and replayed against github by the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteAction {
+    MarkAsRead,
+    /// Marks the thread as done, it leaves the github inbox until there is new activity
+    MarkAsDone,
+}
+
+impl fmt::Display for RemoteAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteAction::MarkAsRead => write!(f, "mark_as_read"),
+            RemoteAction::MarkAsDone => write!(f, "mark_as_done"),
+        }
+    }
+}
+
+impl FromStr for RemoteAction {
+    type Err = anyhow::Error;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "mark_as_read" => Ok(RemoteAction::MarkAsRead),
+            "mark_as_done" => Ok(RemoteAction::MarkAsDone),
+            other => anyhow::bail!("Unknown remote action `{}`", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingAction {
+    pub id: i64,
+    pub thread_id: String,
+    pub action: RemoteAction,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+impl PendingAction {
+    fn from_row(row: &Row) -> Result<Self> {
+        let action: String = row.get("action")?;
+        Ok(PendingAction {
+            id: row.get("id")?,
+            thread_id: row.get("thread_id")?,
+            action: action.parse().map_err(|err: anyhow::Error| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into())
+            })?,
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+impl Cache {
+    pub fn enqueue_action(&self, thread_id: &str, action: RemoteAction) -> Result<()> {
+        self.instance.execute(
+            "INSERT INTO pending_actions (thread_id, action, created_at) VALUES (?1, ?2, ?3)",
+            (thread_id, action.to_string(), timestamp(chrono::Utc::now())),
+        )?;
+        Ok(())
+    }
+
+    pub fn read_pending_actions(&self) -> Result<Vec<PendingAction>> {
+        let mut statement = self.instance.prepare("SELECT * FROM pending_actions ORDER BY id")?;
+        let action_iter = statement.query_map([], PendingAction::from_row)?;
+
+        let mut actions = Vec::<PendingAction>::new();
+        for action in action_iter {
+            actions.push(action?)
+        }
+
+        Ok(actions)
+    }
+
+    pub fn complete_action(&self, id: i64) -> Result<()> {
+        self.instance
+            .execute("DELETE FROM pending_actions WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn fail_action(&self, id: i64, error: &str) -> Result<()> {
+        self.instance.execute(
+            "UPDATE pending_actions SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+            (error, id),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RemoteAction;
+    use crate::cache::Cache;
+
+    #[test]
+    fn queue_and_replay_actions() {
+        let instance = Cache::in_memory().unwrap();
+        instance.enqueue_action("12", RemoteAction::MarkAsRead).unwrap();
+        instance.enqueue_action("13", RemoteAction::MarkAsDone).unwrap();
+
+        let actions = instance.read_pending_actions().unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].thread_id, "12");
+        assert_eq!(actions[0].action, RemoteAction::MarkAsRead);
+
+        instance.fail_action(actions[0].id, "offline").unwrap();
+        instance.complete_action(actions[1].id).unwrap();
+
+        let actions = instance.read_pending_actions().unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].attempts, 1);
+        assert_eq!(actions[0].last_error.as_deref(), Some("offline"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use rusqlite::Result;
+
+use crate::cache::{notifications::timestamp, Cache};
+
+/// Local triage state attached to a thread: a free text note and tags such as `followup`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotation {
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Cache {
+    /// Sets the note on a thread, an empty note removes it.
+    pub fn set_note(&self, thread_id: &str, note: &str) -> Result<()> {
+        if note.trim().is_empty() {
+            self.instance
+                .execute("DELETE FROM notes WHERE thread_id = ?1", [thread_id])?;
+            return self.reindex(thread_id);
+        }
+
+        self.instance.execute(
+            "INSERT INTO notes (thread_id, note, updated_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(thread_id) DO UPDATE SET note = excluded.note, updated_at = excluded.updated_at",
+            (thread_id, note.trim(), timestamp(chrono::Utc::now())),
+        )?;
+        self.reindex(thread_id)
+    }
+
+    pub fn add_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        self.instance.execute(
+            "INSERT OR IGNORE INTO tags (thread_id, tag) VALUES (?1, ?2)",
+            (thread_id, tag.trim()),
+        )?;
+        self.reindex(thread_id)
+    }
+
+    pub fn remove_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        self.instance.execute(
+            "DELETE FROM tags WHERE thread_id = ?1 AND tag = ?2",
+            (thread_id, tag.trim()),
+        )?;
+        self.reindex(thread_id)
+    }
+
+    pub fn read_annotation(&self, thread_id: &str) -> Result<Annotation> {
+        Ok(self.read_annotations()?.remove(thread_id).unwrap_or_default())
+    }
+
+    pub fn read_annotations(&self) -> Result<HashMap<String, Annotation>> {
+        let mut annotations: HashMap<String, Annotation> = HashMap::new();
+
+        let mut statement = self.instance.prepare("SELECT thread_id, note FROM notes")?;
+        let notes = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for note in notes {
+            let (thread_id, note) = note?;
+            annotations.entry(thread_id).or_default().note = Some(note);
+        }
+
+        let mut statement = self
+            .instance
+            .prepare("SELECT thread_id, tag FROM tags ORDER BY thread_id, tag")?;
+        let tags = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for tag in tags {
+            let (thread_id, tag) = tag?;
+            annotations.entry(thread_id).or_default().tags.push(tag);
+        }
+
+        Ok(annotations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::Cache;
+
+    #[test]
+    fn notes_and_tags() {
+        let instance = Cache::in_memory().unwrap();
+        instance.set_note("12", "waiting on infra").unwrap();
+        instance.add_tag("12", "followup").unwrap();
+        instance.add_tag("12", "blocked-on-infra").unwrap();
+        instance.add_tag("12", "followup").unwrap();
+        instance.add_tag("13", "followup").unwrap();
+
+        let annotation = instance.read_annotation("12").unwrap();
+        assert_eq!(annotation.note.as_deref(), Some("waiting on infra"));
+        assert_eq!(annotation.tags, vec!["blocked-on-infra", "followup"]);
+
+        instance.set_note("12", "").unwrap();
+        instance.remove_tag("12", "followup").unwrap();
+        let annotation = instance.read_annotation("12").unwrap();
+        assert_eq!(annotation.note, None);
+        assert_eq!(annotation.tags, vec!["blocked-on-infra"]);
+        assert_eq!(instance.read_annotations().unwrap().len(), 2);
+    }
+}
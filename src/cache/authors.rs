@@ -0,0 +1,38 @@
+use rusqlite::{OptionalExtension, Result};
+
+use crate::cache::Cache;
+
+/// Who opened an issue or pull request, looked up once per subject and shared by every thread about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectAuthor {
+    /// `None` when the lookup failed or github didn't say
+    pub author: Option<String>,
+    /// The `updated_at` of the thread when it was looked up, an unknown author is looked up again after new activity
+    pub updated_at: String,
+}
+
+impl Cache {
+    pub fn read_subject_author(&self, subject_url: &str) -> Result<Option<SubjectAuthor>> {
+        self.instance
+            .query_row(
+                "SELECT author, updated_at FROM subject_authors WHERE subject_url = ?1",
+                [subject_url],
+                |row| {
+                    Ok(SubjectAuthor {
+                        author: row.get("author")?,
+                        updated_at: row.get("updated_at")?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn write_subject_author(&self, subject_url: &str, author: &SubjectAuthor) -> Result<()> {
+        self.instance.execute(
+            "INSERT INTO subject_authors (subject_url, author, updated_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(subject_url) DO UPDATE SET author = excluded.author, updated_at = excluded.updated_at",
+            (subject_url, &author.author, &author.updated_at),
+        )?;
+        Ok(())
+    }
+}
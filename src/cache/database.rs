@@ -1,54 +1,283 @@
+use std::{path::Path, thread, time::Duration};
+
 use anyhow::Result;
-use rusqlite::Connection;
+use rusqlite::{Connection, ErrorCode, TransactionBehavior};
+
+use crate::cache::recovery::{corruption, integrity_check};
+
+/// Ordered schema migrations, the database `user_version` is the number of steps already applied.
+/// Steps are append only, never edit or reorder an existing entry.
+const MIGRATIONS: &[&str] = &[
+    // 1: notifications table, matches the schema created before versioning was introduced
+    "CREATE TABLE IF NOT EXISTS ghostie(
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        repo TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        url TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );",
+    // 2: keep the rest of the github notification payload
+    "ALTER TABLE ghostie ADD COLUMN reason TEXT NOT NULL DEFAULT '';
+    ALTER TABLE ghostie ADD COLUMN unread INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE ghostie ADD COLUMN last_read_at TEXT;
+    ALTER TABLE ghostie ADD COLUMN thread_url TEXT NOT NULL DEFAULT '';
+    ALTER TABLE ghostie ADD COLUMN latest_comment_url TEXT;
+    ALTER TABLE ghostie ADD COLUMN repo_id INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE ghostie ADD COLUMN repo_owner TEXT NOT NULL DEFAULT '';
+    UPDATE ghostie SET
+        thread_url = 'https://api.github.com/notifications/threads/' || id,
+        repo_owner = substr(name, 1, instr(name, '/') - 1);",
+    // 3: threads with activity the user has not looked at yet
+    "ALTER TABLE ghostie ADD COLUMN new_activity INTEGER NOT NULL DEFAULT 0;",
+    // 4: local read/dismissed tombstone, cleared once the thread is updated after it
+    "ALTER TABLE ghostie ADD COLUMN dismissed_at TEXT;",
+    // 5: remote actions waiting to be replayed against github
+    "CREATE TABLE IF NOT EXISTS pending_actions(
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        thread_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        created_at TEXT NOT NULL
+    );",
+    // 6: notifications hidden until a chosen time
+    "ALTER TABLE ghostie ADD COLUMN snoozed_until TEXT;",
+    // 7: notifications kept around regardless of the polling window
+    "ALTER TABLE ghostie ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;",
+    // 8: local notes and tags, keyed by thread id so they outlive the cached thread
+    "CREATE TABLE IF NOT EXISTS notes(
+        thread_id TEXT PRIMARY KEY,
+        note TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tags(
+        thread_id TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (thread_id, tag)
+    );",
+    // 9: full text search over subject, repository, reason and local notes and tags, comment bodies are never
+    // fetched from github so there is nothing else to index
+    "CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(id UNINDEXED, subject, repo, reason, notes);
+    INSERT INTO search_index (id, subject, repo, reason, notes)
+    SELECT
+        ghostie.id,
+        ghostie.subject,
+        ghostie.name,
+        ghostie.reason,
+        COALESCE((SELECT note FROM notes WHERE thread_id = ghostie.id), '') || ' ' ||
+        COALESCE((SELECT group_concat(tag, ' ') FROM tags WHERE thread_id = ghostie.id), '')
+    FROM ghostie;",
+    // 10: window pruning and incremental lookups by last activity
+    "CREATE INDEX IF NOT EXISTS ghostie_updated_at ON ghostie(updated_at);",
+    // 11: small key/value state kept between polls, e.g. the sync cursor
+    "CREATE TABLE IF NOT EXISTS sync_state(
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // 12: who opened the subject of a thread, for rules matching on the author
+    "ALTER TABLE ghostie ADD COLUMN author TEXT;",
+    // 13: authors looked up per subject, so threads about the same subject and later polls don't fetch them again
+    "CREATE TABLE IF NOT EXISTS subject_authors(
+        subject_url TEXT PRIMARY KEY,
+        author TEXT,
+        updated_at TEXT NOT NULL
+    );",
+];
+
+/// How long a connection waits on a lock held by another process (the daemon or the TUI) before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const BUSY_RETRIES: u32 = 5;
 
-use crate::configuration::config::Config;
-extern crate dirs;
+/// Retries an operation that failed because another connection held the database lock, backing off between tries.
+pub fn with_retry<T, F>(mut operation: F) -> rusqlite::Result<T>
+where
+    F: FnMut() -> rusqlite::Result<T>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Err(rusqlite::Error::SqliteFailure(error, _))
+                if matches!(error.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+                    && attempt < BUSY_RETRIES =>
+            {
+                attempt += 1;
+                thread::sleep(Duration::from_millis(50 * 2u64.pow(attempt)));
+            }
+            result => return result,
+        }
+    }
+}
 
 pub struct Database {
     pub connection: Connection,
 }
 
 impl Database {
-    pub fn create() -> Result<Self> {
-        let connection = Self::create_connection();
-        Self::migrate_up(&connection);
+    /// Opens and migrates the cache, failing with `Corrupt` when SQLite reports the file as damaged. Damage SQLite only
+    /// finds by scanning every page is left to `open_verified`.
+    pub fn open(db_file: &Path) -> Result<Self> {
+        Self::open_checked(db_file, false)
+    }
 
-        Ok(Self { connection })
+    /// Like `open`, but runs a full `PRAGMA integrity_check` first. Used when the daemon starts and for backups being
+    /// restored, as it reads the whole file.
+    pub fn open_verified(db_file: &Path) -> Result<Self> {
+        Self::open_checked(db_file, true)
     }
 
-    pub fn destroy() -> Result<Self> {
-        let connection = Self::create_connection();
-        Self::migrate_down(&connection);
+    fn open_checked(db_file: &Path, verify: bool) -> Result<Self> {
+        let mut connection = Connection::open(db_file)
+            .map_err(|err| anyhow::anyhow!("There was an error creating the database connection, error: {}", err))?;
+        Self::configure(&connection).map_err(corruption)?;
+        if verify {
+            integrity_check(&connection)?;
+        }
+
+        let current_version = Self::schema_version(&connection).map_err(corruption)?;
+        if current_version > MIGRATIONS.len() {
+            anyhow::bail!(
+                "The cache schema version {} is newer than the latest known version {}, please upgrade ghostie",
+                current_version,
+                MIGRATIONS.len()
+            );
+        }
+        if current_version < MIGRATIONS.len() {
+            with_retry(|| Self::migrate_up(&mut connection))
+                .map_err(|err| anyhow::anyhow!("There was an error migrating up. Error: {}", err))?;
+        }
 
         Ok(Self { connection })
     }
 
-    fn create_connection() -> Connection {
-        let db_file = &Config::read().cache_file;
-        Connection::open(db_file)
-            .unwrap_or_else(|err| panic!("There was an error creating the database connection, error: {}", err))
+    pub fn schema_version(connection: &Connection) -> rusqlite::Result<usize> {
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(version as usize)
     }
 
-    fn migrate_down(connection: &Connection) {
-        connection
-            .execute("DROP TABLE IF EXISTS ghostie", ())
-            .unwrap_or_else(|err| panic!("There was an error dropping the database, error: {}", err));
+    /// WAL lets the TUI read while the daemon writes, the busy timeout makes writers queue instead of failing.
+    fn configure(connection: &Connection) -> rusqlite::Result<()> {
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        with_retry(|| connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0)))?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        Ok(())
+    }
+
+    /// Applies pending migrations under a write lock, re-reading the version in case another process got there first.
+    /// Only called when migrations are pending, so opening an up to date cache never waits on the daemon's writes.
+    fn migrate_up(connection: &mut Connection) -> rusqlite::Result<()> {
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let current_version = Self::schema_version(&transaction)?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+        }
+        transaction.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rusqlite::Connection;
+
+    use super::{Database, MIGRATIONS};
+
+    fn a_database_file(name: &str) -> PathBuf {
+        let db_file = std::env::temp_dir().join(format!("ghostie-{}-{}.db", name, std::process::id()));
+        std::fs::remove_file(&db_file).ok();
+        db_file
     }
 
-    fn migrate_up(connection: &Connection) {
+    fn create_v0_database(db_file: &PathBuf) {
+        let connection = Connection::open(db_file).unwrap();
         connection
-            .execute(
-                "CREATE TABLE IF NOT EXISTS ghostie(
-                  id TEXT PRIMARY KEY,
-                  name TEXT NOT NULL,
-                  repo TEXT NOT NULL,
-                  subject TEXT NOT NULL,
-                  kind TEXT NOT NULL,
-                  url TEXT NOT NULL,
-                  updated_at TEXT NOT NULL
-              )",
-                (),
+            .execute_batch(
+                "CREATE TABLE ghostie(
+                    id TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    repo TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    url TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );
+                INSERT INTO ghostie VALUES (
+                    '12', 'attriaayush/ghostie', 'ghostie', 'I need review', 'PullRequest',
+                    'https://github.com/attriaayush/ghostie/pull/1', '2022-12-12T18:52:24Z'
+                );",
             )
-            .unwrap_or_else(|err| panic!("There was an error migrating up. Error: {}", err));
+            .unwrap();
+    }
+
+    #[test]
+    fn migrates_fresh_database_to_latest() {
+        let db_file = a_database_file("fresh");
+        let database = Database::open(&db_file).unwrap();
+
+        assert_eq!(
+            Database::schema_version(&database.connection).unwrap(),
+            MIGRATIONS.len()
+        );
+        std::fs::remove_file(&db_file).ok();
+    }
+
+    #[test]
+    fn upgrades_v0_database_without_data_loss() {
+        let db_file = a_database_file("v0");
+        create_v0_database(&db_file);
+
+        let database = Database::open(&db_file).unwrap();
+        assert_eq!(
+            Database::schema_version(&database.connection).unwrap(),
+            MIGRATIONS.len()
+        );
+
+        let (id, subject, updated_at, repo_owner): (String, String, String, String) = database
+            .connection
+            .query_row("SELECT id, subject, updated_at, repo_owner FROM ghostie", [], |row| {
+                Ok((
+                    row.get("id")?,
+                    row.get("subject")?,
+                    row.get("updated_at")?,
+                    row.get("repo_owner")?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(id, "12");
+        assert_eq!(subject, "I need review");
+        assert_eq!(updated_at, "2022-12-12T18:52:24Z");
+        assert_eq!(repo_owner, "attriaayush");
+        std::fs::remove_file(&db_file).ok();
+    }
+
+    #[test]
+    fn reopening_is_idempotent() {
+        let db_file = a_database_file("reopen");
+        create_v0_database(&db_file);
+
+        Database::open(&db_file).unwrap();
+        let database = Database::open_verified(&db_file).unwrap();
+
+        let count: usize = database
+            .connection
+            .query_row("SELECT COUNT(*) FROM ghostie", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        std::fs::remove_file(&db_file).ok();
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let db_file = a_database_file("newer");
+        Connection::open(&db_file)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+
+        assert!(Database::open(&db_file).is_err());
+        std::fs::remove_file(&db_file).ok();
     }
 }
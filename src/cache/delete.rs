@@ -1,5 +1,5 @@
-use crate::cache::Cache;
+use crate::cache::store::NotificationStore;
 
-pub fn prune_all() {
-    Cache::new().delete_all().unwrap();
+pub fn prune_all(store: &dyn NotificationStore) -> anyhow::Result<()> {
+    store.delete_all()
 }
@@ -0,0 +1,475 @@
+use std::{io::Write, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{
+    notifications::{timestamp, Notification},
+    store::NotificationStore,
+    sync::parse_timestamp,
+};
+
+/// File formats `ghostie export` writes and `ghostie import` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// A header row then one row per thread, for spreadsheets
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.trim().to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "ndjson" | "jsonl" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            other => anyhow::bail!("Unknown format `{}`, expected one of json, ndjson or csv", other),
+        }
+    }
+}
+
+impl Format {
+    /// Guesses the format from a file extension, e.g. `backup.ndjson`.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+/// A cached thread with its local triage state, one row or object of an export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    #[serde(flatten)]
+    pub notification: Notification,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+const CSV_COLUMNS: [&str; 21] = [
+    "id",
+    "name",
+    "repo",
+    "subject",
+    "kind",
+    "url",
+    "updated_at",
+    "reason",
+    "unread",
+    "last_read_at",
+    "thread_url",
+    "latest_comment_url",
+    "repo_id",
+    "repo_owner",
+    "author",
+    "new_activity",
+    "dismissed_at",
+    "snoozed_until",
+    "pinned",
+    "note",
+    "tags",
+];
+
+/// Tags are joined in a single CSV cell.
+const CSV_TAG_SEPARATOR: char = ';';
+
+/// Reads every cached thread updated since `since`, most recent first, with its note and tags.
+pub fn records(
+    store: &dyn NotificationStore,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+) -> anyhow::Result<Vec<Record>> {
+    let mut annotations = store.read_annotations()?;
+    let mut notifications: Vec<Notification> = store
+        .read_all()?
+        .into_iter()
+        .filter(|n| match since {
+            Some(since) => parse_timestamp(&n.updated_at)
+                .filter(|updated_at| *updated_at < since)
+                .is_none(),
+            None => true,
+        })
+        .collect();
+    notifications.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+
+    Ok(notifications
+        .into_iter()
+        .map(|notification| {
+            let annotation = annotations.remove(&notification.id).unwrap_or_default();
+            Record {
+                notification,
+                note: annotation.note,
+                tags: annotation.tags,
+            }
+        })
+        .collect())
+}
+
+/// Writes the cache to `out`, returning the number of threads exported.
+pub fn export(
+    store: &dyn NotificationStore,
+    format: Format,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    out: &mut dyn Write,
+) -> anyhow::Result<usize> {
+    let records = records(store, since)?;
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for record in records.iter() {
+                serde_json::to_writer(&mut *out, record)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Csv => {
+            write!(
+                out,
+                "{}\r\n",
+                csv_row(CSV_COLUMNS.iter().map(|column| column.to_string()))
+            )?;
+            for record in records.iter() {
+                write!(out, "{}\r\n", csv_row(to_csv(record)))?;
+            }
+        }
+    }
+
+    Ok(records.len())
+}
+
+/// Parses an export, line numbers in errors are 1-based.
+pub fn parse(format: Format, input: &str) -> anyhow::Result<Vec<Record>> {
+    match format {
+        Format::Json => Ok(serde_json::from_str(input)?),
+        Format::Ndjson => input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|err| anyhow::anyhow!("Line {}: {}", index + 1, err))
+            })
+            .collect(),
+        Format::Csv => {
+            let mut rows = parse_csv(input)?.into_iter();
+            let header = rows
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing the CSV header row"))?;
+            rows.enumerate()
+                .map(|(index, row)| {
+                    from_csv(&header, &row).map_err(|err| anyhow::anyhow!("Row {}: {}", index + 1, err))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Rewrites the timestamps the cache compares as text in its own UTC format, so e.g. an offset or fractional seconds
+/// don't sort apart from the rest. Rows are numbered from 1 as in `parse`.
+fn normalized(records: &[Record]) -> anyhow::Result<Vec<Notification>> {
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let normalize = |column: &str, value: &str| {
+                parse_timestamp(value).map(timestamp).ok_or_else(|| {
+                    anyhow::anyhow!("Row {}: {} `{}` is not an RFC 3339 timestamp", index + 1, column, value)
+                })
+            };
+            let mut notification = record.notification.clone();
+            notification.updated_at = normalize("updated_at", &notification.updated_at)?;
+            notification.dismissed_at = notification
+                .dismissed_at
+                .map(|value| normalize("dismissed_at", &value))
+                .transpose()?;
+            notification.snoozed_until = notification
+                .snoozed_until
+                .map(|value| normalize("snoozed_until", &value))
+                .transpose()?;
+            Ok(notification)
+        })
+        .collect()
+}
+
+/// Restores exported threads with their local state, returning the number imported.
+/// A cached copy with more recent activity than the export is kept, its note and tags are still merged. Nothing is
+/// imported when a row has an invalid timestamp.
+pub fn import(store: &dyn NotificationStore, records: &[Record]) -> anyhow::Result<usize> {
+    let mut imported = vec![];
+    for notification in normalized(records)?.into_iter() {
+        match store.cached_updated_at(&notification.id)? {
+            Some(updated_at) if updated_at > notification.updated_at => {}
+            _ => imported.push(notification),
+        }
+    }
+
+    store.write_batch(&imported)?;
+    for notification in imported.iter() {
+        store.write_local_state(notification)?;
+    }
+    for record in records.iter() {
+        if let Some(note) = &record.note {
+            store.set_note(&record.notification.id, note)?;
+        }
+        for tag in record.tags.iter() {
+            store.add_tag(&record.notification.id, tag)?;
+        }
+    }
+
+    Ok(imported.len())
+}
+
+fn to_csv(record: &Record) -> impl Iterator<Item = String> {
+    let n = &record.notification;
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    [
+        n.id.clone(),
+        n.name.clone(),
+        n.repo.clone(),
+        n.subject.clone(),
+        n.kind.clone(),
+        n.url.clone(),
+        n.updated_at.clone(),
+        n.reason.clone(),
+        n.unread.to_string(),
+        optional(&n.last_read_at),
+        n.thread_url.clone(),
+        optional(&n.latest_comment_url),
+        n.repo_id.to_string(),
+        n.repo_owner.clone(),
+        optional(&n.author),
+        n.new_activity.to_string(),
+        optional(&n.dismissed_at),
+        optional(&n.snoozed_until),
+        n.pinned.to_string(),
+        optional(&record.note),
+        record.tags.join(&CSV_TAG_SEPARATOR.to_string()),
+    ]
+    .into_iter()
+}
+
+/// Builds a record from a CSV row, columns are looked up by header so spreadsheets may reorder them.
+fn from_csv(header: &[String], row: &[String]) -> anyhow::Result<Record> {
+    let cell = |column: &str| -> Option<&str> {
+        header
+            .iter()
+            .position(|name| name == column)
+            .and_then(|index| row.get(index))
+            .map(|value| value.as_str())
+    };
+    let required = |column: &str| -> anyhow::Result<String> {
+        cell(column)
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow::anyhow!("missing the `{}` column", column))
+    };
+    let optional = |column: &str| cell(column).filter(|value| !value.is_empty()).map(str::to_owned);
+    let flag = |column: &str, default: bool| -> anyhow::Result<bool> {
+        match cell(column).filter(|value| !value.is_empty()) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("`{}` is not true or false in the `{}` column", value, column)),
+            None => Ok(default),
+        }
+    };
+
+    Ok(Record {
+        notification: Notification {
+            id: required("id")?,
+            name: required("name")?,
+            repo: required("repo")?,
+            subject: required("subject")?,
+            kind: required("kind")?,
+            url: required("url")?,
+            updated_at: required("updated_at")?,
+            reason: required("reason")?,
+            unread: flag("unread", true)?,
+            last_read_at: optional("last_read_at"),
+            thread_url: required("thread_url")?,
+            latest_comment_url: optional("latest_comment_url"),
+            repo_id: required("repo_id")?
+                .parse()
+                .map_err(|_| anyhow::anyhow!("`repo_id` is not a number"))?,
+            repo_owner: required("repo_owner")?,
+            author: optional("author"),
+            new_activity: flag("new_activity", false)?,
+            dismissed_at: optional("dismissed_at"),
+            snoozed_until: optional("snoozed_until"),
+            pinned: flag("pinned", false)?,
+        },
+        note: optional("note"),
+        tags: cell("tags")
+            .unwrap_or_default()
+            .split(CSV_TAG_SEPARATOR)
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    })
+}
+
+/// Joins fields into a CSV row, quoting those with separators, quotes or line breaks as in RFC 4180.
+fn csv_row(fields: impl Iterator<Item = String>) -> String {
+    fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Splits RFC 4180 CSV into rows of fields, quoted fields may span lines.
+fn parse_csv(input: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        anyhow::bail!("Unterminated quoted field");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| !(row.len() == 1 && row[0].is_empty()));
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, parse, parse_csv, Format};
+    use crate::cache::{
+        notifications::{Notification, NotificationBuilder},
+        store::NotificationStore,
+        MemoryStore,
+    };
+
+    fn a_notification(id: &str, subject: &str) -> Notification {
+        NotificationBuilder::new(id).subject(subject).build()
+    }
+
+    fn a_triaged_store() -> MemoryStore {
+        let store = MemoryStore::default();
+        store
+            .write_batch(&[
+                a_notification("1", "Fix \"quotes\", commas"),
+                a_notification("2", "Plain subject"),
+            ])
+            .unwrap();
+        store.dismiss("1").unwrap();
+        store.set_pinned("2", true).unwrap();
+        store.set_note("1", "first line\nsecond, line").unwrap();
+        store.add_tag("1", "followup").unwrap();
+        store.add_tag("1", "blocked-on-infra").unwrap();
+        store
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        let store = a_triaged_store();
+        for format in [Format::Json, Format::Ndjson, Format::Csv] {
+            let mut out = vec![];
+            assert_eq!(export(&store, format, None, &mut out).unwrap(), 2);
+
+            let records = parse(format, &String::from_utf8(out).unwrap()).unwrap();
+            let restored = MemoryStore::default();
+            assert_eq!(import(&restored, &records).unwrap(), 2);
+
+            let mut expected = store.read_all().unwrap();
+            let mut actual = restored.read_all().unwrap();
+            expected.sort_by(|a, b| a.id.cmp(&b.id));
+            actual.sort_by(|a, b| a.id.cmp(&b.id));
+            assert_eq!(actual, expected, "{:?}", format);
+            assert_eq!(restored.read_annotations().unwrap(), store.read_annotations().unwrap());
+        }
+    }
+
+    #[test]
+    fn keeps_newer_cached_threads() {
+        let store = MemoryStore::default();
+        let mut newer = a_notification("1", "Newer");
+        newer.updated_at = String::from("2022-12-13T08:00:00Z");
+        store.write_batch(&[newer]).unwrap();
+
+        let mut out = vec![];
+        export(&a_triaged_store(), Format::Json, None, &mut out).unwrap();
+        let records = parse(Format::Json, &String::from_utf8(out).unwrap()).unwrap();
+
+        assert_eq!(import(&store, &records).unwrap(), 1);
+        assert_eq!(store.read_by_id("1").unwrap().unwrap().subject, "Newer");
+        assert!(store
+            .read_annotation("1")
+            .unwrap()
+            .tags
+            .contains(&String::from("followup")));
+    }
+
+    #[test]
+    fn normalizes_imported_timestamps() {
+        let mut out = vec![];
+        export(&a_triaged_store(), Format::Json, None, &mut out).unwrap();
+        let mut records = parse(Format::Json, &String::from_utf8(out).unwrap()).unwrap();
+        records[1].notification.snoozed_until = Some(String::from("tomorrow 9am"));
+
+        let store = MemoryStore::default();
+        let err = import(&store, &records).unwrap_err().to_string();
+        assert!(err.starts_with("Row 2: snoozed_until `tomorrow 9am`"), "{}", err);
+        assert!(store.read_all().unwrap().is_empty());
+
+        records[1].notification.snoozed_until = None;
+        records[0].notification.updated_at = String::from("2022-12-12");
+        assert!(import(&store, &records).is_err());
+
+        records[0].notification.updated_at = String::from("2022-12-12T20:52:24.250+02:00");
+        records[0].notification.dismissed_at = Some(String::from("2022-12-13T09:00:00+01:00"));
+        assert_eq!(import(&store, &records).unwrap(), 2);
+        let imported = store.read_by_id(&records[0].notification.id).unwrap().unwrap();
+        assert_eq!(imported.updated_at, "2022-12-12T18:52:24Z");
+        assert_eq!(imported.dismissed_at.as_deref(), Some("2022-12-13T08:00:00Z"));
+    }
+
+    #[test]
+    fn parses_quoted_csv() {
+        let rows = parse_csv("a,\"b,\"\"c\"\"\",\"line\r\nbreak\"\r\n,\r\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    String::from("a"),
+                    String::from("b,\"c\""),
+                    String::from("line\r\nbreak")
+                ],
+                vec![String::new(), String::new()],
+            ]
+        );
+        assert!(parse_csv("\"open").is_err());
+    }
+}
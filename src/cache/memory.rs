@@ -0,0 +1,372 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+use crate::cache::{
+    actions::{PendingAction, RemoteAction},
+    annotations::Annotation,
+    authors::SubjectAuthor,
+    notifications::{timestamp, Notification},
+    retention::Retention,
+    store::NotificationStore,
+};
+
+#[derive(Default)]
+struct State {
+    notifications: BTreeMap<String, Notification>,
+    pending_actions: Vec<PendingAction>,
+    next_action_id: i64,
+    notes: HashMap<String, String>,
+    tags: BTreeMap<String, BTreeSet<String>>,
+    subject_authors: HashMap<String, SubjectAuthor>,
+    sync_state: HashMap<String, String>,
+}
+
+/// A store that lives only as long as the process, for tests and dry runs that must not touch the user's cache.
+#[derive(Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+impl MemoryStore {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn filter<F: Fn(&Notification) -> bool>(&self, predicate: F) -> Vec<Notification> {
+        self.state()
+            .notifications
+            .values()
+            .filter(|notification| predicate(notification))
+            .cloned()
+            .collect()
+    }
+
+    fn update<F: FnOnce(&mut Notification)>(&self, id: &str, update: F) -> bool {
+        match self.state().notifications.get_mut(id) {
+            Some(notification) => {
+                update(notification);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mirrors the weights of the SQLite full text index: subject, repository, reason, then notes and tags.
+    fn score(state: &State, notification: &Notification, query: &[String]) -> Option<u32> {
+        let mut annotations = state.notes.get(&notification.id).cloned().unwrap_or_default();
+        for tag in state.tags.get(&notification.id).into_iter().flatten() {
+            annotations.push(' ');
+            annotations.push_str(tag);
+        }
+        let fields = [
+            (notification.subject.as_str(), 10),
+            (notification.name.as_str(), 5),
+            (notification.reason.as_str(), 1),
+            (annotations.as_str(), 2),
+        ];
+
+        let mut score = 0;
+        for term in query.iter() {
+            let term_score: u32 = fields
+                .iter()
+                .filter(|(text, _)| words(text).any(|word| word.starts_with(term.as_str())))
+                .map(|(_, weight)| weight)
+                .sum();
+            if term_score == 0 {
+                return None;
+            }
+            score += term_score;
+        }
+        Some(score)
+    }
+}
+
+impl NotificationStore for MemoryStore {
+    fn read_all(&self) -> Result<Vec<Notification>> {
+        Ok(self.filter(|_| true))
+    }
+
+    fn read_active(&self) -> Result<Vec<Notification>> {
+        let now = timestamp(Utc::now());
+        Ok(self.filter(|notification| {
+            notification.dismissed_at.is_none()
+                && notification
+                    .snoozed_until
+                    .as_ref()
+                    .filter(|until| **until > now)
+                    .is_none()
+        }))
+    }
+
+    fn read_pinned(&self) -> Result<Vec<Notification>> {
+        let mut notifications = self.filter(|notification| notification.pinned && notification.dismissed_at.is_none());
+        notifications.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(notifications)
+    }
+
+    fn read_snoozed(&self) -> Result<Vec<Notification>> {
+        let now = timestamp(Utc::now());
+        let mut notifications =
+            self.filter(|notification| notification.snoozed_until.as_ref().is_some_and(|until| *until > now));
+        notifications.sort_by(|a, b| a.snoozed_until.cmp(&b.snoozed_until));
+        Ok(notifications)
+    }
+
+    fn read_by_id(&self, id: &str) -> Result<Option<Notification>> {
+        Ok(self.state().notifications.get(id).cloned())
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Notification>> {
+        let query: Vec<String> = words(query).collect();
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let state = self.state();
+        let mut results: Vec<(u32, Notification)> = state
+            .notifications
+            .values()
+            .filter_map(|notification| Some((Self::score(&state, notification, &query)?, notification.clone())))
+            .collect();
+        results.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        Ok(results.into_iter().map(|(_, notification)| notification).collect())
+    }
+
+    fn cached_updated_at(&self, id: &str) -> Result<Option<String>> {
+        Ok(self
+            .state()
+            .notifications
+            .get(id)
+            .map(|notification| notification.updated_at.clone()))
+    }
+
+    fn write_batch(&self, notifications: &[Notification]) -> Result<()> {
+        let mut state = self.state();
+        for notification in notifications.iter() {
+            let mut written = notification.clone();
+            match state.notifications.get(&notification.id) {
+                Some(cached) => {
+                    written.dismissed_at = cached
+                        .dismissed_at
+                        .clone()
+                        .filter(|dismissed_at| notification.updated_at <= *dismissed_at);
+                    written.snoozed_until = cached.snoozed_until.clone();
+                    written.pinned = cached.pinned;
+                    written.author = written.author.or_else(|| cached.author.clone());
+                }
+                None => {
+                    written.dismissed_at = None;
+                    written.snoozed_until = None;
+                    written.pinned = false;
+                }
+            }
+            state.notifications.insert(written.id.clone(), written);
+        }
+        Ok(())
+    }
+
+    fn delete_all(&self) -> Result<()> {
+        self.state().notifications.clear();
+        Ok(())
+    }
+
+    fn delete_by_id(&self, id: &str) -> Result<()> {
+        self.state().notifications.remove(id);
+        Ok(())
+    }
+
+    fn prune(&self, retention: Retention, now: DateTime<Utc>) -> Result<usize> {
+        let expired_before = timestamp(now - chrono::Duration::days(retention.read_days.into()));
+        let prunable = |notification: &Notification| {
+            !notification.pinned && notification.snoozed_until.is_none() && !notification.unread
+        };
+
+        let mut state = self.state();
+        let count = state.notifications.len();
+        state
+            .notifications
+            .retain(|_, notification| !prunable(notification) || notification.updated_at >= expired_before);
+
+        let kept = state.notifications.values().filter(|n| !prunable(n)).count();
+        let mut over_cap: Vec<&Notification> = state.notifications.values().filter(|n| prunable(n)).collect();
+        over_cap.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        let over_cap: Vec<String> = over_cap
+            .into_iter()
+            .skip((retention.max_notifications as usize).saturating_sub(kept))
+            .map(|notification| notification.id.clone())
+            .collect();
+        for id in over_cap.iter() {
+            state.notifications.remove(id);
+        }
+
+        Ok(count - state.notifications.len())
+    }
+
+    fn dismiss(&self, id: &str) -> Result<()> {
+        self.update(id, |notification| {
+            notification.dismissed_at = Some(timestamp(Utc::now()));
+            notification.new_activity = false;
+        });
+        Ok(())
+    }
+
+    fn mark_all_as_seen(&self) -> Result<()> {
+        for notification in self.state().notifications.values_mut() {
+            notification.new_activity = false;
+        }
+        Ok(())
+    }
+
+    fn mark_read_remotely(&self, ids: &[String], now: DateTime<Utc>) -> Result<usize> {
+        Ok(ids
+            .iter()
+            .filter(|id| {
+                self.update(id, |cached| {
+                    cached.unread = false;
+                    cached.new_activity = false;
+                    cached.dismissed_at = cached.dismissed_at.take().or_else(|| Some(timestamp(now)));
+                })
+            })
+            .count())
+    }
+
+    fn write_local_state(&self, notification: &Notification) -> Result<bool> {
+        Ok(self.update(&notification.id, |cached| {
+            cached.dismissed_at = notification.dismissed_at.clone();
+            cached.snoozed_until = notification.snoozed_until.clone();
+            cached.pinned = notification.pinned;
+            cached.new_activity = notification.new_activity;
+        }))
+    }
+
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        Ok(self.update(id, |notification| notification.pinned = pinned))
+    }
+
+    fn snooze(&self, id: &str, until: DateTime<Utc>) -> Result<bool> {
+        Ok(self.update(id, |notification| notification.snoozed_until = Some(timestamp(until))))
+    }
+
+    fn unsnooze(&self, id: &str) -> Result<bool> {
+        Ok(self.update(id, |notification| notification.snoozed_until = None))
+    }
+
+    fn wake_snoozed(&self, now: DateTime<Utc>) -> Result<Vec<Notification>> {
+        let now = timestamp(now);
+        let mut woken = vec![];
+        for notification in self.state().notifications.values_mut() {
+            if notification.snoozed_until.as_ref().is_some_and(|until| *until <= now) {
+                woken.push(notification.clone());
+                notification.snoozed_until = None;
+                notification.dismissed_at = None;
+                notification.new_activity = true;
+            }
+        }
+        Ok(woken)
+    }
+
+    fn enqueue_action(&self, thread_id: &str, action: RemoteAction) -> Result<()> {
+        let mut state = self.state();
+        state.next_action_id += 1;
+        let id = state.next_action_id;
+        state.pending_actions.push(PendingAction {
+            id,
+            thread_id: thread_id.to_owned(),
+            action,
+            attempts: 0,
+            last_error: None,
+            created_at: timestamp(Utc::now()),
+        });
+        Ok(())
+    }
+
+    fn read_pending_actions(&self) -> Result<Vec<PendingAction>> {
+        Ok(self.state().pending_actions.clone())
+    }
+
+    fn complete_action(&self, id: i64) -> Result<()> {
+        self.state().pending_actions.retain(|action| action.id != id);
+        Ok(())
+    }
+
+    fn fail_action(&self, id: i64, error: &str) -> Result<()> {
+        if let Some(action) = self.state().pending_actions.iter_mut().find(|action| action.id == id) {
+            action.attempts += 1;
+            action.last_error = Some(error.to_owned());
+        }
+        Ok(())
+    }
+
+    fn set_note(&self, thread_id: &str, note: &str) -> Result<()> {
+        let mut state = self.state();
+        if note.trim().is_empty() {
+            state.notes.remove(thread_id);
+        } else {
+            state.notes.insert(thread_id.to_owned(), note.trim().to_owned());
+        }
+        Ok(())
+    }
+
+    fn add_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        self.state()
+            .tags
+            .entry(thread_id.to_owned())
+            .or_default()
+            .insert(tag.trim().to_owned());
+        Ok(())
+    }
+
+    fn remove_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        let mut state = self.state();
+        if let Some(tags) = state.tags.get_mut(thread_id) {
+            tags.remove(tag.trim());
+            if tags.is_empty() {
+                state.tags.remove(thread_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_annotations(&self) -> Result<HashMap<String, Annotation>> {
+        let state = self.state();
+        let mut annotations: HashMap<String, Annotation> = HashMap::new();
+        for (thread_id, note) in state.notes.iter() {
+            annotations.entry(thread_id.clone()).or_default().note = Some(note.clone());
+        }
+        for (thread_id, tags) in state.tags.iter() {
+            annotations.entry(thread_id.clone()).or_default().tags = tags.iter().cloned().collect();
+        }
+        Ok(annotations)
+    }
+
+    fn read_subject_author(&self, subject_url: &str) -> Result<Option<SubjectAuthor>> {
+        Ok(self.state().subject_authors.get(subject_url).cloned())
+    }
+
+    fn write_subject_author(&self, subject_url: &str, author: &SubjectAuthor) -> Result<()> {
+        self.state()
+            .subject_authors
+            .insert(subject_url.to_owned(), author.clone());
+        Ok(())
+    }
+
+    fn read_state(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.state().sync_state.get(key).cloned())
+    }
+
+    fn write_state(&self, key: &str, value: &str) -> Result<()> {
+        self.state().sync_state.insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+}
@@ -1,6 +1,19 @@
+pub use self::database::with_retry;
+pub use self::memory::MemoryStore;
 pub use self::notifications::Cache;
+pub use self::store::NotificationStore;
 
+pub mod actions;
+pub mod annotations;
+pub mod authors;
 mod database;
 pub mod delete;
+pub mod export;
+pub mod memory;
 pub mod notifications;
 pub mod read;
+pub mod recovery;
+pub mod retention;
+pub mod search;
+pub mod store;
+pub mod sync;
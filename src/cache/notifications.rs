@@ -1,9 +1,12 @@
-use rusqlite::{Connection, Result};
+use std::path::Path;
 
-use crate::cache::database::Database;
+use rusqlite::{Connection, OptionalExtension, Result, Row, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{database::Database, recovery::Corrupt};
 use crate::github::notifications::Notification as GithubNotification;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Notification {
     pub id: String,
     pub name: String,
@@ -12,6 +15,50 @@ pub struct Notification {
     pub kind: String,
     pub url: String,
     pub updated_at: String,
+    pub reason: String,
+    pub unread: bool,
+    pub last_read_at: Option<String>,
+    pub thread_url: String,
+    pub latest_comment_url: Option<String>,
+    pub repo_id: u32,
+    pub repo_owner: String,
+    /// Who opened the issue or pull request, only fetched when a rule matches on it
+    pub author: Option<String>,
+    pub new_activity: bool,
+    pub dismissed_at: Option<String>,
+    pub snoozed_until: Option<String>,
+    pub pinned: bool,
+}
+
+impl Notification {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Notification {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            repo: row.get("repo")?,
+            subject: row.get("subject")?,
+            kind: row.get("kind")?,
+            url: row.get("url")?,
+            updated_at: row.get("updated_at")?,
+            reason: row.get("reason")?,
+            unread: row.get("unread")?,
+            last_read_at: row.get("last_read_at")?,
+            thread_url: row.get("thread_url")?,
+            latest_comment_url: row.get("latest_comment_url")?,
+            repo_id: row.get("repo_id")?,
+            repo_owner: row.get("repo_owner")?,
+            author: row.get("author")?,
+            new_activity: row.get("new_activity")?,
+            dismissed_at: row.get("dismissed_at")?,
+            snoozed_until: row.get("snoozed_until")?,
+            pinned: row.get("pinned")?,
+        })
+    }
+}
+
+/// Formats timestamps the way github does, so they compare correctly with `updated_at` as text.
+pub fn timestamp(datetime: chrono::DateTime<chrono::Utc>) -> String {
+    datetime.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
 }
 
 fn create_github_url(repo_url: String, subject_url: Option<String>) -> String {
@@ -38,6 +85,18 @@ impl From<GithubNotification> for Notification {
             kind: github_notification.subject.kind,
             url: create_github_url(github_notification.repository.html_url, github_notification.subject.url),
             updated_at: github_notification.updated_at,
+            reason: github_notification.reason,
+            unread: github_notification.unread,
+            last_read_at: github_notification.last_read_at,
+            thread_url: github_notification.url,
+            latest_comment_url: github_notification.subject.latest_comment_url,
+            repo_id: github_notification.repository.id,
+            repo_owner: github_notification.repository.owner.login,
+            author: None,
+            new_activity: true,
+            dismissed_at: None,
+            snoozed_until: None,
+            pinned: false,
         }
     }
 }
@@ -46,60 +105,83 @@ pub struct Cache {
     pub instance: Connection,
 }
 
-impl Default for Cache {
-    fn default() -> Self {
-        Self::new()
+impl Cache {
+    /// Opens the cache, a corrupt file is moved aside and the cache restored from a backup or rebuilt.
+    pub fn open(db_file: &Path) -> anyhow::Result<Self> {
+        Self::recover_from(db_file, Database::open(db_file))
     }
-}
 
-impl Cache {
-    pub fn new() -> Self {
-        let db_client = Database::create().unwrap();
-        Self {
-            instance: db_client.connection,
-        }
+    /// Opens the cache after a full integrity check, for the background process which keeps it open from then on.
+    pub fn open_verified(db_file: &Path) -> anyhow::Result<Self> {
+        Self::recover_from(db_file, Database::open_verified(db_file))
     }
 
-    pub fn destroy() -> Result<Self> {
-        let db_client = Database::destroy().unwrap();
-        Ok(Self {
-            instance: db_client.connection,
-        })
+    fn recover_from(db_file: &Path, opened: anyhow::Result<Database>) -> anyhow::Result<Self> {
+        match opened {
+            Err(err) if err.is::<Corrupt>() && db_file.is_file() => Self::recover(db_file, err),
+            result => Ok(Self {
+                instance: result?.connection,
+            }),
+        }
     }
 
-    pub fn delete_all_before(&mut self, timestamp: chrono::DateTime<chrono::Utc>) {
-        let notifications = self.read_all().unwrap();
-        notifications
-            .iter()
-            .filter(|n| chrono::DateTime::parse_from_rfc3339(&n.updated_at).unwrap() < timestamp)
-            .for_each(|notification| self.delete_by_id(&notification.id).unwrap());
+    /// A private SQLite database that disappears with the connection.
+    pub fn in_memory() -> anyhow::Result<Self> {
+        Self::open(Path::new(":memory:"))
     }
 
-    pub fn delete_all(&mut self) -> Result<()> {
-        self.instance.execute("DELETE FROM ghostie", [])?;
+    pub fn delete_all(&self) -> Result<()> {
+        self.instance
+            .execute_batch("DELETE FROM ghostie; DELETE FROM search_index;")?;
         Ok(())
     }
 
     pub fn delete_by_id(&self, id: &str) -> Result<()> {
         self.instance
             .execute("DELETE FROM ghostie WHERE id = :id", &[(":id", &id.to_owned())])?;
+        self.instance
+            .execute("DELETE FROM search_index WHERE id = :id", &[(":id", &id.to_owned())])?;
 
         Ok(())
     }
 
     pub fn read_all(&self) -> Result<Vec<Notification>> {
         let mut statement = self.instance.prepare("SELECT * FROM ghostie")?;
-        let notification_iter = statement.query_map([], |row| {
-            Ok(Notification {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                repo: row.get(2)?,
-                subject: row.get(3)?,
-                kind: row.get(4)?,
-                url: row.get(5)?,
-                updated_at: row.get(6)?,
-            })
-        })?;
+        let notification_iter = statement.query_map([], Notification::from_row)?;
+
+        let mut notifications = Vec::<Notification>::new();
+        for notification in notification_iter {
+            notifications.push(notification?)
+        }
+
+        Ok(notifications)
+    }
+
+    /// Notifications that have not been read, dismissed or snoozed locally.
+    pub fn read_active(&self) -> Result<Vec<Notification>> {
+        self.read_where(
+            "SELECT * FROM ghostie WHERE dismissed_at IS NULL AND (snoozed_until IS NULL OR snoozed_until <= ?1)",
+            [timestamp(chrono::Utc::now())],
+        )
+    }
+
+    pub fn read_pinned(&self) -> Result<Vec<Notification>> {
+        self.read_where(
+            "SELECT * FROM ghostie WHERE pinned = 1 AND dismissed_at IS NULL ORDER BY updated_at DESC",
+            [],
+        )
+    }
+
+    pub fn read_snoozed(&self) -> Result<Vec<Notification>> {
+        self.read_where(
+            "SELECT * FROM ghostie WHERE snoozed_until > ?1 ORDER BY snoozed_until",
+            [timestamp(chrono::Utc::now())],
+        )
+    }
+
+    pub(crate) fn read_where<P: rusqlite::Params>(&self, query: &str, params: P) -> Result<Vec<Notification>> {
+        let mut statement = self.instance.prepare(query)?;
+        let notification_iter = statement.query_map(params, Notification::from_row)?;
 
         let mut notifications = Vec::<Notification>::new();
         for notification in notification_iter {
@@ -110,70 +192,240 @@ impl Cache {
     }
 
     pub fn read_by_id(&self, id: &str) -> Result<Notification> {
-        let notification = self
-            .instance
-            .query_row("SELECT * FROM ghostie WHERE id = :id", &[(":id", id)], |row| {
-                Ok(Notification {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    repo: row.get(2)?,
-                    subject: row.get(3)?,
-                    kind: row.get(4)?,
-                    url: row.get(5)?,
-                    updated_at: row.get(6)?,
-                })
-            })?;
+        let notification = self.instance.query_row(
+            "SELECT * FROM ghostie WHERE id = :id",
+            &[(":id", id)],
+            Notification::from_row,
+        )?;
 
         Ok(notification)
     }
 
     pub fn write(&self, notification: &Notification) -> Result<()> {
         self.instance.execute(
-            "INSERT OR REPLACE INTO ghostie (id, name, repo, subject, kind, url, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            (
-              notification.id.to_owned(),
-              notification.name.to_owned(),
-              notification.repo.to_owned(),
-              notification.subject.to_owned(),
-              notification.kind.to_owned(),
-              notification.url.to_owned(),
-              notification.updated_at.to_owned()),
+            "INSERT INTO ghostie (
+                id, name, repo, subject, kind, url, updated_at, reason, unread,
+                last_read_at, thread_url, latest_comment_url, repo_id, repo_owner, new_activity,
+                author
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                repo = excluded.repo,
+                subject = excluded.subject,
+                kind = excluded.kind,
+                url = excluded.url,
+                updated_at = excluded.updated_at,
+                reason = excluded.reason,
+                unread = excluded.unread,
+                last_read_at = excluded.last_read_at,
+                thread_url = excluded.thread_url,
+                latest_comment_url = excluded.latest_comment_url,
+                repo_id = excluded.repo_id,
+                repo_owner = excluded.repo_owner,
+                new_activity = excluded.new_activity,
+                author = COALESCE(excluded.author, ghostie.author),
+                dismissed_at = CASE
+                    WHEN excluded.updated_at > ghostie.dismissed_at THEN NULL
+                    ELSE ghostie.dismissed_at
+                END",
+            rusqlite::params![
+                notification.id,
+                notification.name,
+                notification.repo,
+                notification.subject,
+                notification.kind,
+                notification.url,
+                notification.updated_at,
+                notification.reason,
+                notification.unread,
+                notification.last_read_at,
+                notification.thread_url,
+                notification.latest_comment_url,
+                notification.repo_id,
+                notification.repo_owner,
+                notification.new_activity,
+                notification.author,
+            ],
+        )?;
+        self.reindex(&notification.id)?;
+
+        Ok(())
+    }
+
+    /// Hides the notification locally until github reports activity on the thread after this point.
+    pub fn dismiss(&self, id: &str) -> Result<()> {
+        self.instance.execute(
+            "UPDATE ghostie SET dismissed_at = ?1, new_activity = 0 WHERE id = ?2",
+            (timestamp(chrono::Utc::now()), id),
+        )?;
+        Ok(())
+    }
+
+    /// Overwrites the local state of a cached thread, read, snoozed, pinned and new activity, e.g. when importing.
+    pub fn write_local_state(&self, notification: &Notification) -> Result<usize> {
+        self.instance.execute(
+            "UPDATE ghostie SET dismissed_at = ?1, snoozed_until = ?2, pinned = ?3, new_activity = ?4 WHERE id = ?5",
+            rusqlite::params![
+                notification.dismissed_at,
+                notification.snoozed_until,
+                notification.pinned,
+                notification.new_activity,
+                notification.id,
+            ],
+        )
+    }
+
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> Result<usize> {
+        self.instance
+            .execute("UPDATE ghostie SET pinned = ?1 WHERE id = ?2", (pinned, id))
+    }
+
+    pub fn snooze(&self, id: &str, until: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        self.instance.execute(
+            "UPDATE ghostie SET snoozed_until = ?1 WHERE id = ?2",
+            (timestamp(until), id),
+        )
+    }
+
+    pub fn unsnooze(&self, id: &str) -> Result<usize> {
+        self.instance
+            .execute("UPDATE ghostie SET snoozed_until = NULL WHERE id = ?1", [id])
+    }
+
+    /// Brings back notifications whose snooze has expired as unread, returning them.
+    pub fn wake_snoozed(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<Notification>> {
+        let now = timestamp(now);
+        let woken = self.read_where("SELECT * FROM ghostie WHERE snoozed_until <= ?1", [&now])?;
+        self.instance.execute(
+            "UPDATE ghostie SET snoozed_until = NULL, dismissed_at = NULL, new_activity = 1 WHERE snoozed_until <= ?1",
+            [&now],
         )?;
 
+        Ok(woken)
+    }
+
+    pub fn mark_all_as_seen(&self) -> Result<()> {
+        self.instance
+            .execute("UPDATE ghostie SET new_activity = 0 WHERE new_activity = 1", [])?;
         Ok(())
     }
 
+    /// Marks threads read on github, e.g. from the website or a phone, as read and hides them like a local read.
+    /// They come back once github reports new activity on them.
+    pub fn mark_read_remotely(&self, ids: &[String], now: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        let transaction = Transaction::new_unchecked(&self.instance, TransactionBehavior::Immediate)?;
+        let mut updated = 0;
+        {
+            let mut statement = transaction.prepare(
+                "UPDATE ghostie SET unread = 0, new_activity = 0, dismissed_at = COALESCE(dismissed_at, ?2)
+                WHERE id = ?1",
+            )?;
+            for id in ids.iter() {
+                updated += statement.execute((id, timestamp(now)))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(updated)
+    }
+
+    /// The `updated_at` of a cached thread, if it is cached at all.
+    pub fn cached_updated_at(&self, id: &str) -> Result<Option<String>> {
+        self.instance
+            .query_row("SELECT updated_at FROM ghostie WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+    }
+
+    /// Upserts all notifications in a single transaction, either every row is written or none is.
     pub fn write_batch(&self, notifications: &[Notification]) -> Result<()> {
+        let transaction = Transaction::new_unchecked(&self.instance, TransactionBehavior::Immediate)?;
         for notification in notifications.iter() {
             Self::write(self, notification)?
         }
-        Ok(())
+        transaction.commit()
     }
 }
 
+/// Notifications for tests, each field has a fixed default until overridden, e.g.
+/// `NotificationBuilder::new("1").reason("mention").build()`.
 #[cfg(test)]
-mod tests {
-    use super::{Cache, Notification};
-    use fake::{Fake as Generate, Faker};
-    use serial_test::serial;
+pub(crate) struct NotificationBuilder(Notification);
+
+#[cfg(test)]
+impl NotificationBuilder {
+    pub fn new(id: &str) -> Self {
+        Self(Notification {
+            id: id.to_owned(),
+            name: String::from("octocat/Hello-World"),
+            repo: String::from("Hello-World"),
+            subject: String::from("I need review"),
+            kind: String::from("PullRequest"),
+            url: String::from("https://github.com/"),
+            updated_at: String::from("2022-12-12T18:52:24Z"),
+            reason: String::from("review_requested"),
+            unread: true,
+            last_read_at: None,
+            thread_url: format!("https://api.github.com/notifications/threads/{}", id),
+            latest_comment_url: None,
+            repo_id: 1296269,
+            repo_owner: String::from("octocat"),
+            author: None,
+            new_activity: true,
+            dismissed_at: None,
+            snoozed_until: None,
+            pinned: false,
+        })
+    }
 
-    struct Fake(Notification);
+    /// The full `owner/name` of the repository, the owner and name are split from it.
+    pub fn name(mut self, name: &str) -> Self {
+        let (owner, repo) = name.split_once('/').unwrap_or(("octocat", name));
+        self.0.name = name.to_owned();
+        self.0.repo = repo.to_owned();
+        self.0.repo_owner = owner.to_owned();
+        self
+    }
 
-    fn clear_cache() {
-        Cache::destroy().unwrap();
+    pub fn unread(mut self, unread: bool) -> Self {
+        self.0.unread = unread;
+        self
     }
 
+    pub fn updated_at(mut self, updated_at: &str) -> Self {
+        self.0.updated_at = updated_at.to_owned();
+        self
+    }
+
+    pub fn reason(mut self, reason: &str) -> Self {
+        self.0.reason = reason.to_owned();
+        self
+    }
+
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.0.subject = subject.to_owned();
+        self
+    }
+
+    pub fn author(mut self, author: &str) -> Self {
+        self.0.author = Some(author.to_owned());
+        self
+    }
+
+    pub fn build(self) -> Notification {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cache, GithubNotification, Notification, NotificationBuilder};
+    use crate::cache::{retention::Retention, with_retry};
+    use fake::{Fake as Generate, Faker};
+
+    struct Fake;
+
     impl Fake {
         fn a_notification(id: String) -> Notification {
-            Notification {
-                id,
-                name: String::from("Fake Notification"),
-                repo: String::from("Fake repo"),
-                kind: String::from("pull_request"),
-                subject: String::from("I need review"),
-                url: String::from("https://github.com/"),
-                updated_at: String::from("2022-12-12T18:52:24Z"),
-            }
+            NotificationBuilder::new(&id).build()
         }
 
         fn list_of_notifications(count: usize) -> Vec<Notification> {
@@ -190,23 +442,8 @@ mod tests {
     const _ID: &str = "12";
 
     #[test]
-    #[serial]
-    fn delete_by_timestamp() {
-        clear_cache();
-        let mut instance = Cache::new();
-        instance.write(&Fake::a_notification(_ID.to_owned())).unwrap();
-
-        instance.delete_all_before(chrono::offset::Utc::now());
-
-        let notification = instance.read_by_id(_ID).is_ok();
-        println!("{}", notification);
-    }
-
-    #[test]
-    #[serial]
     fn write_and_read() {
-        clear_cache();
-        let instance = Cache::new();
+        let instance = Cache::in_memory().unwrap();
         let write = instance.write(&Fake::a_notification(_ID.to_owned())).is_ok();
 
         assert!(write);
@@ -216,10 +453,8 @@ mod tests {
     }
 
     #[test]
-    #[serial]
     fn write_and_delete() {
-        clear_cache();
-        let instance = Cache::new();
+        let instance = Cache::in_memory().unwrap();
         instance.write(&Fake::a_notification(_ID.to_owned())).unwrap();
 
         let deleted = instance.delete_by_id(_ID).is_ok();
@@ -230,15 +465,191 @@ mod tests {
     }
 
     #[test]
-    #[serial]
     fn batch_write_and_read() {
-        clear_cache();
         let count = 5;
-        let instance = Cache::new();
+        let instance = Cache::in_memory().unwrap();
         let write = instance.write_batch(&Fake::list_of_notifications(count)).is_ok();
         assert!(write);
 
         let notifications = instance.read_all().unwrap();
         assert_eq!(count, notifications.len());
     }
+
+    #[test]
+    fn keeps_the_github_payload() {
+        let payload = r#"{
+            "id": "1",
+            "unread": false,
+            "reason": "review_requested",
+            "updated_at": "2022-12-12T18:52:24Z",
+            "last_read_at": "2022-12-12T19:00:00Z",
+            "url": "https://api.github.com/notifications/threads/1",
+            "subject": {
+                "title": "Greetings",
+                "url": "https://api.github.com/repos/octocat/Hello-World/pulls/123",
+                "latest_comment_url": "https://api.github.com/repos/octocat/Hello-World/issues/comments/123",
+                "type": "PullRequest"
+            },
+            "repository": {
+                "id": 1296269,
+                "node_id": "MDEwOlJlcG9zaXRvcnkxMjk2MjY5",
+                "name": "Hello-World",
+                "full_name": "octocat/Hello-World",
+                "html_url": "https://github.com/octocat/Hello-World",
+                "owner": { "login": "octocat" }
+            }
+        }"#;
+        let github_notification: GithubNotification = serde_json::from_str(payload).unwrap();
+        let notification: Notification = github_notification.into();
+
+        assert_eq!(notification.url, "https://github.com/octocat/Hello-World/pull/123");
+        assert_eq!(notification.reason, "review_requested");
+        assert!(!notification.unread);
+        assert_eq!(notification.last_read_at.as_deref(), Some("2022-12-12T19:00:00Z"));
+        assert_eq!(
+            notification.thread_url,
+            "https://api.github.com/notifications/threads/1"
+        );
+        assert_eq!(
+            notification.latest_comment_url.as_deref(),
+            Some("https://api.github.com/repos/octocat/Hello-World/issues/comments/123")
+        );
+        assert_eq!(notification.repo_id, 1296269);
+        assert_eq!(notification.repo_owner, "octocat");
+    }
+
+    #[test]
+    fn write_and_read_payload() {
+        let instance = Cache::in_memory().unwrap();
+        let mut notification = Fake::a_notification(_ID.to_owned());
+        notification.unread = false;
+        notification.last_read_at = Some(String::from("2022-12-12T19:00:00Z"));
+        instance.write(&notification).unwrap();
+
+        let cached = instance.read_by_id(_ID).unwrap();
+        assert_eq!(cached.reason, "review_requested");
+        assert!(!cached.unread);
+        assert_eq!(cached.last_read_at, notification.last_read_at);
+        assert_eq!(cached.repo_id, 1296269);
+        assert_eq!(cached.repo_owner, "octocat");
+    }
+
+    #[test]
+    fn write_updates_existing_thread() {
+        let instance = Cache::in_memory().unwrap();
+        instance.write(&Fake::a_notification(_ID.to_owned())).unwrap();
+        instance.mark_all_as_seen().unwrap();
+
+        let mut updated = Fake::a_notification(_ID.to_owned());
+        updated.updated_at = String::from("2022-12-13T08:00:00Z");
+        updated.subject = String::from("I still need review");
+        instance.write(&updated).unwrap();
+
+        let notifications = instance.read_all().unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].updated_at, "2022-12-13T08:00:00Z");
+        assert_eq!(notifications[0].subject, "I still need review");
+        assert!(notifications[0].new_activity);
+    }
+
+    #[test]
+    fn dismissed_thread_stays_hidden_until_updated() {
+        let instance = Cache::in_memory().unwrap();
+        let mut notification = Fake::a_notification(_ID.to_owned());
+        notification.updated_at = super::timestamp(chrono::Utc::now() - chrono::Duration::hours(1));
+        instance.write(&notification).unwrap();
+        instance.dismiss(_ID).unwrap();
+
+        instance.write(&notification).unwrap();
+        assert!(instance.read_active().unwrap().is_empty());
+        assert_eq!(instance.read_all().unwrap().len(), 1);
+
+        notification.updated_at = super::timestamp(chrono::Utc::now() + chrono::Duration::hours(1));
+        instance.write(&notification).unwrap();
+        assert_eq!(instance.read_active().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn snoozed_thread_comes_back_unread() {
+        let instance = Cache::in_memory().unwrap();
+        instance.write(&Fake::a_notification(_ID.to_owned())).unwrap();
+        instance.dismiss(_ID).unwrap();
+
+        let until = chrono::Utc::now() + chrono::Duration::hours(2);
+        instance.snooze(_ID, until).unwrap();
+        assert!(instance.read_active().unwrap().is_empty());
+        assert_eq!(instance.read_snoozed().unwrap().len(), 1);
+
+        assert!(instance.wake_snoozed(chrono::Utc::now()).unwrap().is_empty());
+        let woken = instance.wake_snoozed(until).unwrap();
+        assert_eq!(woken.len(), 1);
+
+        let notification = instance.read_by_id(_ID).unwrap();
+        assert!(notification.snoozed_until.is_none());
+        assert!(notification.dismissed_at.is_none());
+        assert!(notification.new_activity);
+    }
+
+    #[test]
+    fn pinned_thread_survives_pruning() {
+        let instance = Cache::in_memory().unwrap();
+        let mut notifications = Fake::list_of_notifications(2);
+        notifications.push(Fake::a_notification(_ID.to_owned()));
+        for notification in notifications.iter_mut() {
+            notification.unread = false;
+        }
+        instance.write_batch(&notifications).unwrap();
+        instance.set_pinned(_ID, true).unwrap();
+
+        instance
+            .prune(Retention::default(), chrono::offset::Utc::now())
+            .unwrap();
+
+        let notifications = instance.read_all().unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].id, _ID);
+        assert_eq!(instance.read_pinned().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_poller_and_tui() {
+        let db_file = std::env::temp_dir().join(format!("ghostie-concurrency-{}.db", std::process::id()));
+        std::fs::remove_file(&db_file).ok();
+        Cache::open(&db_file).unwrap();
+
+        let poller_db_file = db_file.clone();
+        let poller = std::thread::spawn(move || {
+            let cache = Cache::open(&poller_db_file).unwrap();
+            for round in 0..20 {
+                let mut notifications = Fake::list_of_notifications(25);
+                for (index, notification) in notifications.iter_mut().enumerate() {
+                    notification.id = format!("{}", index);
+                    notification.updated_at = format!("2022-12-12T18:{:02}:00Z", round);
+                }
+                with_retry(|| cache.write_batch(&notifications)).unwrap();
+                with_retry(|| cache.prune(Retention::default(), chrono::Utc::now())).unwrap();
+            }
+        });
+
+        let tui_db_file = db_file.clone();
+        let tui = std::thread::spawn(move || {
+            for round in 0..100 {
+                let cache = Cache::open(&tui_db_file).unwrap();
+                let id = format!("{}", round % 25);
+                with_retry(|| cache.dismiss(&id)).unwrap();
+                with_retry(|| cache.set_pinned(&id, round % 2 == 0)).unwrap();
+                with_retry(|| cache.add_tag(&id, "followup")).unwrap();
+                cache.read_active().unwrap();
+            }
+        });
+
+        poller.join().unwrap();
+        tui.join().unwrap();
+
+        let cache = Cache::open(&db_file).unwrap();
+        assert_eq!(cache.read_all().unwrap().len(), 25);
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", db_file.display(), suffix)).ok();
+        }
+    }
 }
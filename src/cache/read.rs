@@ -1,9 +1,80 @@
-use crate::cache::{notifications::Notification, Cache};
+use std::collections::HashMap;
 
-pub fn read_all_notifications() -> Vec<Notification> {
-    Cache::new().read_all().unwrap()
+use anyhow::Result;
+
+use crate::cache::{
+    actions::{PendingAction, RemoteAction},
+    annotations::Annotation,
+    notifications::Notification,
+    store::NotificationStore,
+};
+
+pub fn read_all_notifications(store: &dyn NotificationStore) -> Result<Vec<Notification>> {
+    store.read_active()
+}
+
+pub fn read_notification(store: &dyn NotificationStore, id: &str) -> Result<Option<Notification>> {
+    store.read_by_id(id)
+}
+
+pub fn mark_as_read(store: &dyn NotificationStore, id: &str) -> Result<()> {
+    store.dismiss(id)
+}
+
+pub fn mark_all_as_seen(store: &dyn NotificationStore) -> Result<()> {
+    store.mark_all_as_seen()
+}
+
+/// Hides the notification straight away and queues marking it as read on github.
+pub fn queue_mark_as_read(store: &dyn NotificationStore, id: &str) -> Result<()> {
+    store.dismiss(id)?;
+    store.enqueue_action(id, RemoteAction::MarkAsRead)
+}
+
+pub fn read_pending_actions(store: &dyn NotificationStore) -> Result<Vec<PendingAction>> {
+    store.read_pending_actions()
+}
+
+pub fn read_snoozed_notifications(store: &dyn NotificationStore) -> Result<Vec<Notification>> {
+    store.read_snoozed()
+}
+
+pub fn snooze(store: &dyn NotificationStore, id: &str, until: chrono::DateTime<chrono::Utc>) -> Result<bool> {
+    store.snooze(id, until)
+}
+
+pub fn unsnooze(store: &dyn NotificationStore, id: &str) -> Result<bool> {
+    store.unsnooze(id)
+}
+
+pub fn read_pinned_notifications(store: &dyn NotificationStore) -> Result<Vec<Notification>> {
+    store.read_pinned()
+}
+
+pub fn set_pinned(store: &dyn NotificationStore, id: &str, pinned: bool) -> Result<bool> {
+    store.set_pinned(id, pinned)
+}
+
+pub fn read_annotations(store: &dyn NotificationStore) -> Result<HashMap<String, Annotation>> {
+    store.read_annotations()
+}
+
+pub fn read_annotation(store: &dyn NotificationStore, id: &str) -> Result<Annotation> {
+    store.read_annotation(id)
+}
+
+pub fn set_note(store: &dyn NotificationStore, id: &str, note: &str) -> Result<()> {
+    store.set_note(id, note)
+}
+
+pub fn add_tag(store: &dyn NotificationStore, id: &str, tag: &str) -> Result<()> {
+    store.add_tag(id, tag)
+}
+
+pub fn remove_tag(store: &dyn NotificationStore, id: &str, tag: &str) -> Result<()> {
+    store.remove_tag(id, tag)
 }
 
-pub fn mark_as_read(id: &str) {
-    Cache::new().delete_by_id(id).unwrap();
+pub fn search_notifications(store: &dyn NotificationStore, query: &str) -> Result<Vec<Notification>> {
+    store.search(query)
 }
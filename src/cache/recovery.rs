@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use rusqlite::{Connection, ErrorCode};
+
+use crate::cache::{database::Database, notifications::timestamp, Cache};
+use crate::error;
+
+/// Rotated backups kept next to the cache, the oldest is removed first.
+pub const BACKUPS_KEPT: usize = 3;
+
+/// How often the background process snapshots the cache.
+pub const BACKUP_INTERVAL_HOURS: i64 = 24;
+
+/// Recorded when the cache had to be restored or rebuilt, the next poll then resyncs every unread thread.
+pub const RECOVERED_AT: &str = "recovered_at";
+
+const BACKUP_TIMESTAMP: &str = "%Y%m%dT%H%M%SZ";
+
+/// The cache file can't be trusted, raised by `Database::open` so it can recover.
+#[derive(Debug, thiserror::Error)]
+#[error("The cache is corrupt, {0}")]
+pub struct Corrupt(pub String);
+
+/// Maps SQLite errors meaning the file itself is damaged to `Corrupt`, other errors are passed through.
+pub fn corruption(err: rusqlite::Error) -> anyhow::Error {
+    match &err {
+        rusqlite::Error::SqliteFailure(error, _)
+            if matches!(error.code, ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt) =>
+        {
+            Corrupt(err.to_string()).into()
+        }
+        _ => err.into(),
+    }
+}
+
+/// Runs `PRAGMA integrity_check`, failing with the problems SQLite found.
+pub fn integrity_check(connection: &Connection) -> Result<()> {
+    let mut statement = connection.prepare("PRAGMA integrity_check").map_err(corruption)?;
+    let problems = statement
+        .query_map([], |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(corruption)?;
+
+    match problems.as_slice() {
+        [ok] if ok == "ok" => Ok(()),
+        _ => Err(Corrupt(problems.join("; ")).into()),
+    }
+}
+
+fn backup_dir(db_file: &Path) -> PathBuf {
+    db_file.with_file_name("backups")
+}
+
+fn file_stem(db_file: &Path) -> String {
+    db_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("notifications"))
+}
+
+/// Backups of `db_file` with the time they were taken, newest first.
+pub fn backups(db_file: &Path) -> Vec<(chrono::DateTime<chrono::Utc>, PathBuf)> {
+    let prefix = format!("{}-", file_stem(db_file));
+    let mut backups: Vec<_> = fs::read_dir(backup_dir(db_file))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let taken = name.strip_prefix(&prefix)?.strip_suffix(".db")?;
+            let taken = chrono::NaiveDateTime::parse_from_str(taken, BACKUP_TIMESTAMP).ok()?;
+            Some((chrono::DateTime::from_utc(taken, chrono::Utc), path))
+        })
+        .collect();
+    backups.sort_by_key(|(taken, _)| std::cmp::Reverse(*taken));
+    backups
+}
+
+/// Moves a damaged cache, with its WAL and shared memory files, aside so a fresh one can be created.
+pub fn quarantine(db_file: &Path, now: chrono::DateTime<chrono::Utc>) -> Result<PathBuf> {
+    let suffix = format!("corrupt-{}", now.format(BACKUP_TIMESTAMP));
+    let moved = db_file.with_extension(format!("db.{}", suffix));
+    fs::rename(db_file, &moved)?;
+    for sidecar in ["db-wal", "db-shm"] {
+        let path = db_file.with_extension(sidecar);
+        if path.exists() {
+            fs::rename(&path, db_file.with_extension(format!("{}.{}", sidecar, suffix)))?;
+        }
+    }
+
+    Ok(moved)
+}
+
+fn remove_with_sidecars(db_file: &Path) -> Result<()> {
+    fs::remove_file(db_file)?;
+    for sidecar in ["db-wal", "db-shm"] {
+        fs::remove_file(db_file.with_extension(sidecar)).ok();
+    }
+    Ok(())
+}
+
+impl Cache {
+    /// Snapshots the cache into the backups directory with `VACUUM INTO`, keeping the newest `BACKUPS_KEPT`.
+    pub fn backup(&self, db_file: &Path, now: chrono::DateTime<chrono::Utc>) -> Result<PathBuf> {
+        let dir = backup_dir(db_file);
+        fs::create_dir_all(&dir)?;
+        let backup = dir.join(format!("{}-{}.db", file_stem(db_file), now.format(BACKUP_TIMESTAMP)));
+        if !backup.exists() {
+            self.instance.execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
+        }
+
+        for (_, stale) in backups(db_file).into_iter().skip(BACKUPS_KEPT) {
+            fs::remove_file(stale)?;
+        }
+        Ok(backup)
+    }
+
+    /// Takes a backup unless one was taken in the last `BACKUP_INTERVAL_HOURS`.
+    pub fn backup_if_due(&self, db_file: &Path, now: chrono::DateTime<chrono::Utc>) -> Result<Option<PathBuf>> {
+        match backups(db_file).first() {
+            Some((taken, _)) if now - *taken < chrono::Duration::hours(BACKUP_INTERVAL_HOURS) => Ok(None),
+            _ => self.backup(db_file, now).map(Some),
+        }
+    }
+
+    /// Moves a corrupt cache aside and replaces it with the newest backup that opens cleanly, or an empty cache.
+    pub(crate) fn recover(db_file: &Path, err: anyhow::Error) -> Result<Self> {
+        let now = chrono::Utc::now();
+        let moved = quarantine(db_file, now)?;
+        error!(format!("{}, moved it to {}", err, moved.display()));
+
+        let mut restored = None;
+        for (taken, backup) in backups(db_file) {
+            fs::copy(&backup, db_file)?;
+            match Database::open_verified(db_file) {
+                Ok(database) => {
+                    restored = Some((taken, database));
+                    break;
+                }
+                Err(err) => {
+                    error!(format!("Skipping the backup {}, {}", backup.display(), err));
+                    remove_with_sidecars(db_file)?;
+                }
+            }
+        }
+
+        let cache = match restored {
+            Some((taken, database)) => {
+                error!(format!(
+                    "Restored the cache from the backup taken {}, unread notifications are fetched again on the next sync",
+                    taken.with_timezone(&chrono::Local).to_rfc2822()
+                ));
+                Self {
+                    instance: database.connection,
+                }
+            }
+            None => {
+                error!("Rebuilt an empty cache, unread notifications are fetched again on the next sync");
+                Self {
+                    instance: Database::open(db_file)?.connection,
+                }
+            }
+        };
+        cache.mark_recovered(now)?;
+        Ok(cache)
+    }
+
+    /// Marks the cache as recovered so the next poll fetches every unread thread again.
+    pub(crate) fn mark_recovered(&self, now: chrono::DateTime<chrono::Utc>) -> rusqlite::Result<()> {
+        self.write_state(RECOVERED_AT, &timestamp(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::{Duration, Utc};
+
+    use super::{backups, BACKUPS_KEPT, RECOVERED_AT};
+    use crate::cache::{store::NotificationStore, Cache};
+
+    fn a_data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ghostie-recovery-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rotates_backups() {
+        let dir = a_data_dir("rotate");
+        let db_file = dir.join("notifications.db");
+        let cache = Cache::open(&db_file).unwrap();
+        let now = Utc::now();
+
+        assert!(cache.backup_if_due(&db_file, now).unwrap().is_some());
+        assert!(cache
+            .backup_if_due(&db_file, now + Duration::hours(1))
+            .unwrap()
+            .is_none());
+        for day in 1..=4 {
+            cache.backup(&db_file, now + Duration::days(day)).unwrap();
+        }
+
+        let kept = backups(&db_file);
+        assert_eq!(kept.len(), BACKUPS_KEPT);
+        assert_eq!(kept[0].0.timestamp(), (now + Duration::days(4)).timestamp());
+        assert!(Cache::open(&kept[0].1).is_ok());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn restores_a_corrupt_cache_from_the_latest_backup() {
+        let dir = a_data_dir("restore");
+        let db_file = dir.join("notifications.db");
+        let cache = Cache::open(&db_file).unwrap();
+        cache.write_state("last_sync", "2022-12-12T18:52:24Z").unwrap();
+        cache.backup(&db_file, Utc::now()).unwrap();
+        drop(cache);
+
+        std::fs::write(&db_file, b"definitely not a sqlite database, just some garbage bytes").unwrap();
+        let cache = Cache::open(&db_file).unwrap();
+
+        assert_eq!(
+            NotificationStore::read_state(&cache, "last_sync").unwrap().as_deref(),
+            Some("2022-12-12T18:52:24Z")
+        );
+        assert!(NotificationStore::read_state(&cache, RECOVERED_AT).unwrap().is_some());
+        assert!(std::fs::read_dir(&dir).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains(".corrupt-")));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rebuilds_a_corrupt_cache_without_backups() {
+        let dir = a_data_dir("rebuild");
+        let db_file = dir.join("notifications.db");
+        std::fs::write(&db_file, b"definitely not a sqlite database, just some garbage bytes").unwrap();
+
+        let cache = Cache::open(&db_file).unwrap();
+        assert!(NotificationStore::read_all(&cache).unwrap().is_empty());
+        assert!(NotificationStore::read_state(&cache, RECOVERED_AT).unwrap().is_some());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
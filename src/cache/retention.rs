@@ -0,0 +1,120 @@
+use rusqlite::{Result, Transaction, TransactionBehavior};
+
+use crate::cache::{notifications::timestamp, Cache};
+
+/// How long the cache keeps threads, independently of the polling window. Pinned and snoozed threads are always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// Read threads are pruned once they have had no activity for this many days
+    pub read_days: u32,
+    /// Beyond this many cached threads the oldest read ones are pruned, unread threads are never pruned
+    pub max_notifications: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            read_days: 30,
+            max_notifications: 5000,
+        }
+    }
+}
+
+/// Threads the retention policy may prune: read on github, and neither pinned nor snoozed. A thread only read locally
+/// is kept, its `dismissed_at` tombstone hides it again when a full sync fetches it while still unread on github.
+const PRUNABLE: &str = "pinned = 0 AND snoozed_until IS NULL AND unread = 0";
+
+impl Cache {
+    /// Applies the retention policy, returning the number of threads pruned.
+    pub fn prune(&self, retention: Retention, now: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        let transaction = Transaction::new_unchecked(&self.instance, TransactionBehavior::Immediate)?;
+        let expired = transaction.execute(
+            &format!("DELETE FROM ghostie WHERE {} AND updated_at < ?1", PRUNABLE),
+            [timestamp(now - chrono::Duration::days(retention.read_days.into()))],
+        )?;
+        let over_cap = transaction.execute(
+            &format!(
+                "DELETE FROM ghostie WHERE id IN (
+                    SELECT id FROM ghostie WHERE {0}
+                    ORDER BY updated_at DESC
+                    LIMIT -1 OFFSET max(0, ?1 - (SELECT COUNT(*) FROM ghostie WHERE NOT ({0})))
+                )",
+                PRUNABLE
+            ),
+            [retention.max_notifications],
+        )?;
+        transaction.execute("DELETE FROM search_index WHERE id NOT IN (SELECT id FROM ghostie)", [])?;
+        transaction.commit()?;
+
+        Ok(expired + over_cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::Retention;
+    use crate::cache::{
+        memory::MemoryStore,
+        notifications::{timestamp, Notification, NotificationBuilder},
+        store::NotificationStore,
+        Cache,
+    };
+
+    fn a_notification(id: &str, days_old: i64, unread: bool) -> Notification {
+        NotificationBuilder::new(id)
+            .subject(&format!("Thread {}", id))
+            .updated_at(&timestamp(Utc::now() - Duration::days(days_old)))
+            .unread(unread)
+            .build()
+    }
+
+    fn cached_ids(store: &dyn NotificationStore) -> Vec<String> {
+        let mut ids: Vec<String> = store.read_all().unwrap().into_iter().map(|n| n.id).collect();
+        ids.sort();
+        ids
+    }
+
+    fn prunes_read_threads(store: &dyn NotificationStore) {
+        store
+            .write_batch(&[
+                a_notification("old-unread", 400, true),
+                a_notification("old-read", 40, false),
+                a_notification("old-dismissed", 40, true),
+                a_notification("old-pinned", 40, false),
+                a_notification("recent-read", 1, false),
+                a_notification("older-read", 2, false),
+            ])
+            .unwrap();
+        store.dismiss("old-dismissed").unwrap();
+        store.set_pinned("old-pinned", true).unwrap();
+
+        let retention = Retention {
+            read_days: 30,
+            max_notifications: 4,
+        };
+        assert_eq!(store.prune(retention, Utc::now()).unwrap(), 2);
+        assert_eq!(
+            cached_ids(store),
+            vec!["old-dismissed", "old-pinned", "old-unread", "recent-read"]
+        );
+
+        let retention = Retention {
+            read_days: 30,
+            max_notifications: 1,
+        };
+        assert_eq!(store.prune(retention, Utc::now()).unwrap(), 1);
+        assert_eq!(cached_ids(store), vec!["old-dismissed", "old-pinned", "old-unread"]);
+    }
+
+    #[test]
+    fn sqlite_retention() {
+        prunes_read_threads(&Cache::in_memory().unwrap());
+    }
+
+    #[test]
+    fn memory_retention() {
+        prunes_read_threads(&MemoryStore::default());
+    }
+}
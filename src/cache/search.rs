@@ -0,0 +1,94 @@
+use rusqlite::Result;
+
+use crate::cache::{notifications::Notification, Cache};
+
+/// Turns free text into an FTS5 query matching every word as a prefix, so user input can't break the syntax.
+fn match_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Cache {
+    /// Rebuilds the full text index entry of a thread from its cached notification, note and tags. Comment bodies are
+    /// not indexed, the cache only holds what the notifications API returns.
+    pub fn reindex(&self, id: &str) -> Result<()> {
+        self.instance.execute("DELETE FROM search_index WHERE id = ?1", [id])?;
+        self.instance.execute(
+            "INSERT INTO search_index (id, subject, repo, reason, notes)
+            SELECT
+                ghostie.id,
+                ghostie.subject,
+                ghostie.name,
+                ghostie.reason,
+                COALESCE((SELECT note FROM notes WHERE thread_id = ghostie.id), '') || ' ' ||
+                COALESCE((SELECT group_concat(tag, ' ') FROM tags WHERE thread_id = ghostie.id), '')
+            FROM ghostie WHERE ghostie.id = ?1",
+            [id],
+        )?;
+        Ok(())
+    }
+
+    /// Searches every cached notification, read or not, best matches first.
+    pub fn search(&self, query: &str) -> Result<Vec<Notification>> {
+        let query = match_query(query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.read_where(
+            "SELECT ghostie.* FROM search_index
+            JOIN ghostie ON ghostie.id = search_index.id
+            WHERE search_index MATCH ?1
+            ORDER BY bm25(search_index, 0.0, 10.0, 5.0, 1.0, 2.0)",
+            [query],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::match_query;
+    use crate::cache::{
+        notifications::{Notification, NotificationBuilder},
+        Cache,
+    };
+
+    fn a_notification(id: &str, subject: &str, name: &str) -> Notification {
+        NotificationBuilder::new(id).subject(subject).name(name).build()
+    }
+
+    #[test]
+    fn quotes_user_input() {
+        assert_eq!(match_query("db migration"), "\"db\"* \"migration\"*");
+        assert_eq!(match_query("say \"hi\" OR"), "\"say\"* \"\"\"hi\"\"\"* \"OR\"*");
+        assert_eq!(match_query("  "), "");
+    }
+
+    #[test]
+    fn search_ranks_subject_matches_first() {
+        let instance = Cache::in_memory().unwrap();
+        instance
+            .write(&a_notification("1", "Bump serde", "octocat/migration-tools"))
+            .unwrap();
+        instance
+            .write(&a_notification("2", "Run the schema migration", "octocat/api"))
+            .unwrap();
+        instance
+            .write(&a_notification("3", "Fix the login page", "octocat/web"))
+            .unwrap();
+        instance.dismiss("2").unwrap();
+
+        let results = instance.search("migrat").unwrap();
+        let ids: Vec<&str> = results.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "1"]);
+
+        instance.add_tag("3", "followup").unwrap();
+        assert_eq!(instance.search("followup").unwrap()[0].id, "3");
+
+        instance.delete_by_id("3").unwrap();
+        assert!(instance.search("followup").unwrap().is_empty());
+    }
+}
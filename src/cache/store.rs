@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+use crate::cache::{
+    actions::{PendingAction, RemoteAction},
+    annotations::Annotation,
+    authors::SubjectAuthor,
+    notifications::{timestamp, Notification},
+    retention::Retention,
+    sync::{parse_timestamp, PollHealth, SyncCursor},
+    with_retry, Cache,
+};
+
+/// Everything the poller, the CLI and the TUI need from the local notification cache.
+pub trait NotificationStore {
+    fn read_all(&self) -> Result<Vec<Notification>>;
+
+    /// Notifications that have not been read, dismissed or snoozed locally.
+    fn read_active(&self) -> Result<Vec<Notification>>;
+
+    fn read_pinned(&self) -> Result<Vec<Notification>>;
+
+    fn read_snoozed(&self) -> Result<Vec<Notification>>;
+
+    fn read_by_id(&self, id: &str) -> Result<Option<Notification>>;
+
+    /// Searches every cached notification, read or not, best matches first.
+    fn search(&self, query: &str) -> Result<Vec<Notification>>;
+
+    /// The `updated_at` of a cached thread, if it is cached at all.
+    fn cached_updated_at(&self, id: &str) -> Result<Option<String>>;
+
+    /// Upserts all notifications, either every one is written or none is.
+    fn write_batch(&self, notifications: &[Notification]) -> Result<()>;
+
+    fn delete_all(&self) -> Result<()>;
+
+    fn delete_by_id(&self, id: &str) -> Result<()>;
+
+    /// Applies the retention policy, returning the number of threads pruned.
+    fn prune(&self, retention: Retention, now: DateTime<Utc>) -> Result<usize>;
+
+    /// Hides the notification locally until github reports activity on the thread after this point.
+    fn dismiss(&self, id: &str) -> Result<()>;
+
+    fn mark_all_as_seen(&self) -> Result<()>;
+
+    /// Overwrites the local state of a cached thread, read, snoozed, pinned and new activity, e.g. when importing.
+    fn write_local_state(&self, notification: &Notification) -> Result<bool>;
+
+    /// Marks threads read on github as read and hides them until github reports new activity, returning how many
+    /// were cached.
+    fn mark_read_remotely(&self, ids: &[String], now: DateTime<Utc>) -> Result<usize>;
+
+    /// The setters below return whether the thread is cached.
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool>;
+
+    fn snooze(&self, id: &str, until: DateTime<Utc>) -> Result<bool>;
+
+    fn unsnooze(&self, id: &str) -> Result<bool>;
+
+    /// Brings back notifications whose snooze has expired as unread, returning them.
+    fn wake_snoozed(&self, now: DateTime<Utc>) -> Result<Vec<Notification>>;
+
+    fn enqueue_action(&self, thread_id: &str, action: RemoteAction) -> Result<()>;
+
+    fn read_pending_actions(&self) -> Result<Vec<PendingAction>>;
+
+    fn complete_action(&self, id: i64) -> Result<()>;
+
+    fn fail_action(&self, id: i64, error: &str) -> Result<()>;
+
+    /// Sets the note on a thread, an empty note removes it.
+    fn set_note(&self, thread_id: &str, note: &str) -> Result<()>;
+
+    fn add_tag(&self, thread_id: &str, tag: &str) -> Result<()>;
+
+    fn remove_tag(&self, thread_id: &str, tag: &str) -> Result<()>;
+
+    fn read_annotations(&self) -> Result<HashMap<String, Annotation>>;
+
+    fn read_annotation(&self, thread_id: &str) -> Result<Annotation> {
+        Ok(self.read_annotations()?.remove(thread_id).unwrap_or_default())
+    }
+
+    /// The author last looked up for an issue or pull request, by its API url.
+    fn read_subject_author(&self, subject_url: &str) -> Result<Option<SubjectAuthor>>;
+
+    fn write_subject_author(&self, subject_url: &str, author: &SubjectAuthor) -> Result<()>;
+
+    /// Small pieces of state kept between polls, keyed by name.
+    fn read_state(&self, key: &str) -> Result<Option<String>>;
+
+    fn write_state(&self, key: &str, value: &str) -> Result<()>;
+
+    fn read_sync_cursor(&self) -> Result<Option<SyncCursor>> {
+        let last_sync = match self.read_state("last_sync")?.as_deref().and_then(parse_timestamp) {
+            Some(last_sync) => last_sync,
+            None => return Ok(None),
+        };
+        let server_date = self.read_state("server_date")?.as_deref().and_then(parse_timestamp);
+
+        Ok(Some(SyncCursor { last_sync, server_date }))
+    }
+
+    fn write_sync_cursor(&self, cursor: &SyncCursor) -> Result<()> {
+        self.write_state("last_sync", &timestamp(cursor.last_sync))?;
+        self.write_state("server_date", &cursor.server_date.map(timestamp).unwrap_or_default())
+    }
+
+    fn read_poll_health(&self) -> Result<PollHealth> {
+        Ok(PollHealth {
+            consecutive_failures: self
+                .read_state("consecutive_failures")?
+                .and_then(|failures| failures.parse().ok())
+                .unwrap_or_default(),
+            last_error: self.read_state("last_error")?.filter(|error| !error.is_empty()),
+            last_error_at: self.read_state("last_error_at")?.as_deref().and_then(parse_timestamp),
+        })
+    }
+
+    fn record_poll_success(&self) -> Result<()> {
+        self.write_state("consecutive_failures", "0")
+    }
+
+    /// Records a failed poll, returning how many failed in a row.
+    fn record_poll_failure(&self, error: &str, at: DateTime<Utc>) -> Result<u32> {
+        let failures = self.read_poll_health()?.consecutive_failures + 1;
+        self.write_state("consecutive_failures", &failures.to_string())?;
+        self.write_state("last_error", error)?;
+        self.write_state("last_error_at", &timestamp(at))?;
+        Ok(failures)
+    }
+}
+
+/// The SQLite cache shared by the daemon and the TUI, writes are retried while another process holds the lock.
+impl NotificationStore for Cache {
+    fn read_all(&self) -> Result<Vec<Notification>> {
+        Ok(Cache::read_all(self)?)
+    }
+
+    fn read_active(&self) -> Result<Vec<Notification>> {
+        Ok(Cache::read_active(self)?)
+    }
+
+    fn read_pinned(&self) -> Result<Vec<Notification>> {
+        Ok(Cache::read_pinned(self)?)
+    }
+
+    fn read_snoozed(&self) -> Result<Vec<Notification>> {
+        Ok(Cache::read_snoozed(self)?)
+    }
+
+    fn read_by_id(&self, id: &str) -> Result<Option<Notification>> {
+        Ok(Cache::read_by_id(self, id).optional()?)
+    }
+
+    fn search(&self, query: &str) -> Result<Vec<Notification>> {
+        Ok(Cache::search(self, query)?)
+    }
+
+    fn cached_updated_at(&self, id: &str) -> Result<Option<String>> {
+        Ok(Cache::cached_updated_at(self, id)?)
+    }
+
+    fn write_batch(&self, notifications: &[Notification]) -> Result<()> {
+        Ok(with_retry(|| Cache::write_batch(self, notifications))?)
+    }
+
+    fn delete_all(&self) -> Result<()> {
+        Ok(with_retry(|| Cache::delete_all(self))?)
+    }
+
+    fn delete_by_id(&self, id: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::delete_by_id(self, id))?)
+    }
+
+    fn prune(&self, retention: Retention, now: DateTime<Utc>) -> Result<usize> {
+        Ok(with_retry(|| Cache::prune(self, retention, now))?)
+    }
+
+    fn dismiss(&self, id: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::dismiss(self, id))?)
+    }
+
+    fn mark_all_as_seen(&self) -> Result<()> {
+        Ok(with_retry(|| Cache::mark_all_as_seen(self))?)
+    }
+
+    fn write_local_state(&self, notification: &Notification) -> Result<bool> {
+        Ok(with_retry(|| Cache::write_local_state(self, notification))? > 0)
+    }
+
+    fn mark_read_remotely(&self, ids: &[String], now: DateTime<Utc>) -> Result<usize> {
+        Ok(with_retry(|| Cache::mark_read_remotely(self, ids, now))?)
+    }
+
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        Ok(with_retry(|| Cache::set_pinned(self, id, pinned))? > 0)
+    }
+
+    fn snooze(&self, id: &str, until: DateTime<Utc>) -> Result<bool> {
+        Ok(with_retry(|| Cache::snooze(self, id, until))? > 0)
+    }
+
+    fn unsnooze(&self, id: &str) -> Result<bool> {
+        Ok(with_retry(|| Cache::unsnooze(self, id))? > 0)
+    }
+
+    fn wake_snoozed(&self, now: DateTime<Utc>) -> Result<Vec<Notification>> {
+        Ok(with_retry(|| Cache::wake_snoozed(self, now))?)
+    }
+
+    fn enqueue_action(&self, thread_id: &str, action: RemoteAction) -> Result<()> {
+        Ok(with_retry(|| Cache::enqueue_action(self, thread_id, action))?)
+    }
+
+    fn read_pending_actions(&self) -> Result<Vec<PendingAction>> {
+        Ok(Cache::read_pending_actions(self)?)
+    }
+
+    fn complete_action(&self, id: i64) -> Result<()> {
+        Ok(with_retry(|| Cache::complete_action(self, id))?)
+    }
+
+    fn fail_action(&self, id: i64, error: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::fail_action(self, id, error))?)
+    }
+
+    fn set_note(&self, thread_id: &str, note: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::set_note(self, thread_id, note))?)
+    }
+
+    fn add_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::add_tag(self, thread_id, tag))?)
+    }
+
+    fn remove_tag(&self, thread_id: &str, tag: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::remove_tag(self, thread_id, tag))?)
+    }
+
+    fn read_annotations(&self) -> Result<HashMap<String, Annotation>> {
+        Ok(Cache::read_annotations(self)?)
+    }
+
+    fn read_subject_author(&self, subject_url: &str) -> Result<Option<SubjectAuthor>> {
+        Ok(Cache::read_subject_author(self, subject_url)?)
+    }
+
+    fn write_subject_author(&self, subject_url: &str, author: &SubjectAuthor) -> Result<()> {
+        Ok(with_retry(|| Cache::write_subject_author(self, subject_url, author))?)
+    }
+
+    fn read_state(&self, key: &str) -> Result<Option<String>> {
+        Ok(Cache::read_state(self, key)?)
+    }
+
+    fn write_state(&self, key: &str, value: &str) -> Result<()> {
+        Ok(with_retry(|| Cache::write_state(self, key, value))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::NotificationStore;
+    use crate::cache::{
+        actions::RemoteAction,
+        authors::SubjectAuthor,
+        memory::MemoryStore,
+        notifications::{timestamp, Notification, NotificationBuilder},
+        retention::Retention,
+        sync::{parse_timestamp, SyncCursor},
+        Cache,
+    };
+
+    fn a_notification(id: &str, subject: &str) -> Notification {
+        NotificationBuilder::new(id)
+            .subject(subject)
+            .updated_at(&timestamp(Utc::now() - Duration::hours(1)))
+            .build()
+    }
+
+    /// Both backends must behave the same, the TUI and the poller only ever see the trait.
+    fn behaves_like_a_store(store: &dyn NotificationStore) {
+        let mut old = a_notification("1", "Bump serde");
+        old.updated_at = timestamp(Utc::now() - Duration::days(30));
+        old.unread = false;
+        store
+            .write_batch(&[
+                old.clone(),
+                a_notification("2", "Run the schema migration"),
+                a_notification("3", "Fix the login page"),
+            ])
+            .unwrap();
+        assert_eq!(store.read_all().unwrap().len(), 3);
+        assert_eq!(store.cached_updated_at("1").unwrap(), Some(old.updated_at.clone()));
+        assert!(store.read_by_id("missing").unwrap().is_none());
+
+        store.dismiss("2").unwrap();
+        assert!(store.snooze("3", Utc::now() + Duration::hours(2)).unwrap());
+        assert!(!store.snooze("missing", Utc::now()).unwrap());
+        assert_eq!(store.read_active().unwrap().len(), 1);
+        assert_eq!(store.read_snoozed().unwrap().len(), 1);
+        assert_eq!(store.wake_snoozed(Utc::now() + Duration::hours(3)).unwrap().len(), 1);
+        assert_eq!(store.read_active().unwrap().len(), 2);
+
+        let read_elsewhere = [String::from("3"), String::from("missing")];
+        assert_eq!(store.mark_read_remotely(&read_elsewhere, Utc::now()).unwrap(), 1);
+        let read = store.read_by_id("3").unwrap().unwrap();
+        assert!(!read.unread && read.dismissed_at.is_some());
+        assert_eq!(store.read_active().unwrap().len(), 1);
+
+        store.add_tag("2", "followup").unwrap();
+        store.set_note("2", "waiting on infra").unwrap();
+        assert_eq!(store.search("migrat").unwrap()[0].id, "2");
+        assert_eq!(store.search("infra").unwrap()[0].id, "2");
+        assert_eq!(store.read_annotation("2").unwrap().tags, vec!["followup"]);
+
+        let retention = Retention {
+            read_days: 1,
+            max_notifications: 100,
+        };
+        assert!(store.set_pinned("1", true).unwrap());
+        assert_eq!(store.prune(retention, Utc::now()).unwrap(), 0);
+        assert!(store.set_pinned("1", false).unwrap());
+        assert_eq!(store.prune(retention, Utc::now()).unwrap(), 1);
+        assert!(store.search("serde").unwrap().is_empty());
+
+        store.enqueue_action("2", RemoteAction::MarkAsRead).unwrap();
+        let pending = store.read_pending_actions().unwrap();
+        store.fail_action(pending[0].id, "offline").unwrap();
+        assert_eq!(store.read_pending_actions().unwrap()[0].attempts, 1);
+        store.complete_action(pending[0].id).unwrap();
+        assert!(store.read_pending_actions().unwrap().is_empty());
+
+        let unknown = SubjectAuthor {
+            author: None,
+            updated_at: String::from("2022-12-12T18:52:24Z"),
+        };
+        assert!(store.read_subject_author("issues/1").unwrap().is_none());
+        store.write_subject_author("issues/1", &unknown).unwrap();
+        assert_eq!(store.read_subject_author("issues/1").unwrap(), Some(unknown));
+        let known = SubjectAuthor {
+            author: Some(String::from("octocat")),
+            updated_at: String::from("2022-12-13T08:00:00Z"),
+        };
+        store.write_subject_author("issues/1", &known).unwrap();
+        assert_eq!(store.read_subject_author("issues/1").unwrap(), Some(known));
+
+        assert!(store.read_sync_cursor().unwrap().is_none());
+        let cursor = SyncCursor {
+            last_sync: parse_timestamp("2022-12-12T18:52:24Z").unwrap(),
+            server_date: None,
+        };
+        store.write_sync_cursor(&cursor).unwrap();
+        assert_eq!(store.read_sync_cursor().unwrap(), Some(cursor));
+
+        let failed_at = parse_timestamp("2022-12-12T19:00:00Z").unwrap();
+        assert_eq!(store.record_poll_failure("offline", failed_at).unwrap(), 1);
+        assert_eq!(store.record_poll_failure("offline", failed_at).unwrap(), 2);
+        store.record_poll_success().unwrap();
+        let health = store.read_poll_health().unwrap();
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_error.as_deref(), Some("offline"));
+        assert_eq!(health.last_error_at, Some(failed_at));
+
+        store.delete_all().unwrap();
+        assert!(store.read_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sqlite_store() {
+        behaves_like_a_store(&Cache::in_memory().unwrap());
+    }
+
+    #[test]
+    fn memory_store() {
+        behaves_like_a_store(&MemoryStore::default());
+    }
+}
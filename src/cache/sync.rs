@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, Result};
+
+use crate::cache::Cache;
+
+/// Where the last successful poll left off, the next poll only asks github for threads updated since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncCursor {
+    /// Local time the last successful poll started
+    pub last_sync: DateTime<Utc>,
+    /// The `Date` github served that poll at, preferred over the local clock which may drift
+    pub server_date: Option<DateTime<Utc>>,
+}
+
+impl SyncCursor {
+    pub fn since(&self) -> DateTime<Utc> {
+        self.server_date.unwrap_or(self.last_sync)
+    }
+}
+
+/// How background polls have been going since the last success, shown by `ghostie status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PollHealth {
+    /// Polls that failed in a row, reset by the next successful poll
+    pub consecutive_failures: u32,
+    /// The most recent failure, kept after recovering
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+pub(crate) fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|datetime| datetime.with_timezone(&Utc))
+}
+
+impl Cache {
+    pub fn read_state(&self, key: &str) -> Result<Option<String>> {
+        self.instance
+            .query_row("SELECT value FROM sync_state WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+    }
+
+    pub fn write_state(&self, key: &str, value: &str) -> Result<()> {
+        self.instance.execute(
+            "INSERT INTO sync_state (key, value) VALUES (?1, ?2)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            (key, value),
+        )?;
+        Ok(())
+    }
+}
@@ -1,18 +1,150 @@
+use std::{collections::HashMap, io::Read, path::PathBuf};
+
 use crate::configuration::config::Config;
 use crate::tui;
 use clap::Command;
 
-use crate::cache::{delete::prune_all, read::read_all_notifications};
-use crate::daemon::daemonize::Daemon;
-use crate::poll;
+use crate::actions;
+use crate::cache::{
+    annotations::Annotation,
+    delete::prune_all,
+    export::{self, Format},
+    notifications::Notification,
+    read::{
+        add_tag, read_all_notifications, read_annotation, read_annotations, read_notification,
+        read_pinned_notifications, read_snoozed_notifications, remove_tag, search_notifications, set_note, set_pinned,
+        snooze, unsnooze,
+    },
+    store::NotificationStore,
+    Cache,
+};
+use crate::daemon::{daemonize::Daemon, signals::Wake};
+use crate::poll::{self, SyncMode};
+use crate::snooze::{parse_date, parse_until};
+
+fn print_notifications(notifications: &[Notification], annotations: &HashMap<String, Annotation>) {
+    for notification in notifications.iter() {
+        let annotation = match annotations.get(&notification.id) {
+            Some(annotation) => {
+                let tags: Vec<String> = annotation.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+                let note = match &annotation.note {
+                    Some(note) => format!(" 📝 {}", note.lines().next().unwrap_or_default()),
+                    None => String::new(),
+                };
+                format!("{}{}", tags.concat(), note)
+            }
+            None => String::new(),
+        };
+        let snoozed = match &notification.snoozed_until {
+            Some(snoozed_until) => format!(" (snoozed until {})", snoozed_until),
+            None => String::new(),
+        };
+        let pinned = match (notification.dismissed_at.is_some(), notification.pinned) {
+            (true, _) => "✓ ",
+            (false, true) => "📌 ",
+            (false, false) => "",
+        };
+        println!(
+            "{}\t{}[{}] {}\t{}{}{}",
+            notification.id,
+            pinned,
+            notification.kind.to_lowercase(),
+            notification.subject,
+            notification.name,
+            snoozed,
+            annotation
+        );
+    }
+}
+
+fn local_time(datetime: chrono::DateTime<chrono::Utc>) -> String {
+    datetime
+        .with_timezone(&chrono::Local)
+        .format("%a %d %b %H:%M")
+        .to_string()
+}
+
+fn print_status(store: &dyn NotificationStore, config: &Config) -> anyhow::Result<()> {
+    match Daemon::running_pid(config) {
+        Some(pid) => println!("Background process\trunning, process id {}", pid),
+        None => println!("Background process\tstopped, run `ghostie start`"),
+    }
+
+    match store.read_sync_cursor()? {
+        Some(cursor) => println!("Last sync\t\t{}", local_time(cursor.last_sync)),
+        None => println!("Last sync\t\tnever"),
+    }
+    let health = store.read_poll_health()?;
+    if health.consecutive_failures > 0 {
+        println!("Failing polls\t\t{} in a row", health.consecutive_failures);
+    }
+    if let (Some(error), Some(at)) = (&health.last_error, health.last_error_at) {
+        println!("Last error\t\t{}, {}", local_time(at), error);
+    }
+
+    let pending_actions = store.read_pending_actions()?;
+    println!(
+        "Cached notifications\t{} unread of {}",
+        store.read_active()?.len(),
+        store.read_all()?.len()
+    );
+    println!(
+        "Pending actions\t\t{}, {} failed at least once",
+        pending_actions.len(),
+        pending_actions
+            .iter()
+            .filter(|pending| pending.last_error.is_some())
+            .count()
+    );
+    Ok(())
+}
+
+/// Lists the cached notifications each rule matches, as if they had just been fetched.
+fn print_rule_matches(store: &dyn NotificationStore, config: &Config) -> anyhow::Result<()> {
+    let rules = config.additional_config.get_rules();
+    if rules.is_empty() {
+        println!(
+            "No rules in {}, add `rule=` lines with `ghostie configure`",
+            config.config_file.display()
+        );
+        return Ok(());
+    }
+
+    let notifications = store.read_all()?;
+    let annotations = store.read_annotations()?;
+    for rule in rules.iter() {
+        let matching: Vec<Notification> = notifications.iter().filter(|n| rule.matches(n)).cloned().collect();
+        println!("{}\t{} matching", rule, matching.len());
+        print_notifications(&matching, &annotations);
+        println!();
+    }
+
+    let unmatched = notifications.iter().filter(|n| rules.evaluate(n).is_none()).count();
+    println!(
+        "{} of {} cached notifications match no rule",
+        unmatched,
+        notifications.len()
+    );
+    if rules.needs_author() && notifications.iter().any(|n| n.author.is_none()) {
+        println!(
+            "Author rules only match notifications whose author was fetched, on new activity since the rule was added"
+        );
+    }
+    Ok(())
+}
 
-pub fn init() {
-    let matches = clap::Command::new("ghostie")
+pub fn command() -> Command<'static> {
+    clap::Command::new("ghostie")
         .about("manage your github notifications in terminal")
         .subcommand_required(true)
         .arg_required_else_help(true)
         .disable_help_flag(true)
         .disable_help_subcommand(true)
+        .arg(
+            clap::arg!(--home <DIR> "Keep config, cache and logs in DIR instead of the default locations")
+                .required(false)
+                .global(true),
+        )
         .subcommand(
             Command::new("view")
                 .short_flag('V')
@@ -23,6 +155,108 @@ pub fn init() {
                 .short_flag('C')
                 .about("Query the count of unread github notifications"),
         )
+        .subcommand(
+            Command::new("open")
+                .short_flag('O')
+                .about("Open a notification in the browser")
+                .arg(clap::arg!(<ID> "Id of the notification thread")),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List cached notifications with their ids")
+                .arg(clap::arg!(--snoozed "Only list snoozed notifications").action(clap::ArgAction::SetTrue))
+                .arg(clap::arg!(--pinned "Only list pinned notifications").action(clap::ArgAction::SetTrue))
+                .arg(clap::arg!(--tag <TAG> "Only list notifications with this tag").required(false)),
+        )
+        .subcommand(
+            Command::new("pin")
+                .about("Pin a notification so it stays at the top and is never pruned")
+                .arg(clap::arg!(<ID> "Id of the notification thread")),
+        )
+        .subcommand(
+            Command::new("unpin")
+                .about("Unpin a notification")
+                .arg(clap::arg!(<ID> "Id of the notification thread")),
+        )
+        .subcommand(
+            Command::new("search")
+                .short_flag('S')
+                .about("Search all cached notifications, including read ones")
+                .arg(clap::arg!(<QUERY> ... "Words to look for in subjects, repositories, reasons, notes and tags")),
+        )
+        .subcommand(
+            Command::new("note")
+                .about("Show or set the note on a notification, an empty note removes it")
+                .arg(clap::arg!(<ID> "Id of the notification thread"))
+                .arg(clap::arg!([NOTE] ... "Text of the note")),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Tag a notification, e.g. followup or blocked-on-infra")
+                .arg(clap::arg!(<ID> "Id of the notification thread"))
+                .arg(clap::arg!(<TAG> ... "Tags to add")),
+        )
+        .subcommand(
+            Command::new("untag")
+                .about("Remove tags from a notification")
+                .arg(clap::arg!(<ID> "Id of the notification thread"))
+                .arg(clap::arg!(<TAG> ... "Tags to remove")),
+        )
+        .subcommand(
+            Command::new("snooze")
+                .about("Hide a notification until a given time, e.g. \"in 2 hours\" or \"tomorrow 9am\"")
+                .arg(clap::arg!(<ID> "Id of the notification thread"))
+                .arg(clap::arg!(<WHEN> ... "When the notification should come back")),
+        )
+        .subcommand(
+            Command::new("unsnooze")
+                .about("Bring back a snoozed notification")
+                .arg(clap::arg!(<ID> "Id of the notification thread")),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Fetch notifications now, in the foreground and without desktop alerts")
+                .arg(
+                    clap::arg!(--backfill "Fetch every unread notification, not only those since the last sync")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::arg!(--since <DATE> "With --backfill, only fetch notifications updated since, e.g. 2022-11-01")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the cache with notes, tags and read state to stdout, e.g. to move it between machines")
+                .arg(
+                    clap::arg!(--format <FORMAT> "Output format")
+                        .required(false)
+                        .value_parser(["json", "ndjson", "csv"])
+                        .default_value("json"),
+                )
+                .arg(clap::arg!(--since <DATE> "Only export notifications updated since, e.g. 2022-11-01").required(false)),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Load an export into the cache, restoring notes, tags and read state")
+                .arg(clap::arg!(<FILE> "Export to load, - reads stdin"))
+                .arg(
+                    clap::arg!(--format <FORMAT> "Input format, guessed from the file extension by default")
+                        .required(false)
+                        .value_parser(["json", "ndjson", "csv"]),
+                ),
+        )
+        .subcommand(
+            Command::new("rules")
+                .about("Work with the notification rules of the configuration file")
+                .subcommand_required(true)
+                .subcommand(Command::new("test").about("Show which cached notifications each rule matches")),
+        )
+        .subcommand(
+            Command::new("reload").about("Make the background process re-read its configuration, e.g. after configure"),
+        )
+        .subcommand(Command::new("refresh").about("Ask the background process to poll now"))
+        .subcommand(Command::new("status").about("Show the background process, the last sync and recent errors"))
         .subcommand(Command::new("start").about("Run ghostie as a background process"))
         .subcommand(Command::new("stop").about("Stop ghostie as a background process"))
         .subcommand(
@@ -37,23 +271,207 @@ pub fn init() {
         )
         .subcommand(Command::new("clear-logs").about("Clear logs from the background process"))
         .subcommand(Command::new("configure").about("Override default configurations"))
-        .get_matches();
+}
+
+/// The `--home` override, it may be given before or after the subcommand, e.g. `ghostie rules test --home DIR`.
+pub fn home(matches: &clap::ArgMatches) -> Option<PathBuf> {
+    matches
+        .subcommand()
+        .and_then(|(_, sub_matches)| home(sub_matches))
+        .or_else(|| matches.get_one::<String>("home").map(PathBuf::from))
+}
 
+pub fn init(config: &Config, matches: &clap::ArgMatches) {
+    match matches.subcommand() {
+        Some(("start", _)) => Daemon::daemonize(config, || poll::start(config.clone())),
+        Some(("stop", _)) => Daemon::stop_daemon(config),
+        Some(("logs", _)) => Daemon::show_logs(config),
+        Some(("clear-logs", _)) => Daemon::clear_logs(config),
+        Some(("configure", _)) => config.edit_additional_config(),
+        Some(("reload", _)) => match config.reload().and_then(|_| Daemon::wake(config, Wake::Reload)) {
+            Ok(true) => println!(
+                "Asked the background process to reload {}",
+                config.config_file.display()
+            ),
+            Ok(false) => eprintln!("The background process is not running, run `ghostie start`"),
+            Err(err) => eprintln!("{}", err),
+        },
+        Some(("refresh", _)) => match Daemon::wake(config, Wake::Refresh) {
+            Ok(true) => println!("Asked the background process to poll now"),
+            Ok(false) => eprintln!("The background process is not running, run `ghostie start` or `ghostie sync`"),
+            Err(err) => eprintln!("{}", err),
+        },
+        _ => match Cache::open(&config.cache_file) {
+            Ok(cache) => run(&cache, config, matches).unwrap_or_else(|err| eprintln!("{}", err)),
+            Err(err) => eprintln!("{}", err),
+        },
+    };
+}
+
+/// Commands working on the local cache, the daemon opens its own connection after forking. Errors reading or writing
+/// the cache are returned to be printed.
+fn run(store: &dyn NotificationStore, config: &Config, matches: &clap::ArgMatches) -> anyhow::Result<()> {
     match matches.subcommand() {
         Some(("count", _)) => {
-            let notifications = read_all_notifications();
+            let notifications = read_all_notifications(store)?;
             println!("{}", notifications.len())
         }
-        Some(("prune", _)) => prune_all(),
-        Some(("start", _)) => Daemon::daemonize(poll::start),
-        Some(("stop", _)) => Daemon::stop_daemon(),
-        Some(("logs", _)) => Daemon::show_logs(),
-        Some(("clear-logs", _)) => Daemon::clear_logs(),
-        Some(("configure", _)) => Config::edit_additional_config(),
-        Some(("view", _)) => {
-            tui::terminal::open().unwrap();
+        Some(("open", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            match read_notification(store, id)? {
+                Some(notification) => {
+                    actions::open(store, config, &notification).unwrap_or_else(|err| eprintln!("{}", err))
+                }
+                None => eprintln!("No notification with id {} in the local cache", id),
+            }
         }
+        Some(("list", sub_matches)) => {
+            let mut notifications = if sub_matches.get_flag("snoozed") {
+                read_snoozed_notifications(store)?
+            } else if sub_matches.get_flag("pinned") {
+                read_pinned_notifications(store)?
+            } else {
+                let mut notifications = read_all_notifications(store)?;
+                notifications.sort_by(|a, b| b.pinned.cmp(&a.pinned).then(b.updated_at.cmp(&a.updated_at)));
+                notifications
+            };
+
+            let annotations = read_annotations(store)?;
+            if let Some(tag) = sub_matches.get_one::<String>("tag") {
+                let tag = tag.trim_start_matches('#');
+                notifications.retain(|n| annotations.get(&n.id).is_some_and(|a| a.tags.iter().any(|t| t == tag)));
+            }
+            print_notifications(&notifications, &annotations);
+        }
+        Some(("search", sub_matches)) => {
+            let query = sub_matches
+                .get_many::<String>("QUERY")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+            print_notifications(&search_notifications(store, &query)?, &read_annotations(store)?);
+        }
+        Some(("note", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            match sub_matches.get_many::<String>("NOTE") {
+                Some(note) => set_note(store, id, &note.cloned().collect::<Vec<_>>().join(" "))?,
+                None => {
+                    if let Some(note) = read_annotation(store, id)?.note {
+                        println!("{}", note)
+                    }
+                }
+            }
+        }
+        Some((command @ ("tag" | "untag"), sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            for tag in sub_matches.get_many::<String>("TAG").unwrap() {
+                let tag = tag.trim_start_matches('#');
+                if command == "tag" {
+                    add_tag(store, id, tag)?;
+                } else {
+                    remove_tag(store, id, tag)?;
+                }
+            }
+        }
+        Some(("snooze", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            let when = sub_matches
+                .get_many::<String>("WHEN")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let until = parse_until(&when)?;
+            if snooze(store, id, until)? {
+                println!(
+                    "Snoozed {} until {}",
+                    id,
+                    until.with_timezone(&chrono::Local).format("%a %d %b %H:%M")
+                )
+            } else {
+                eprintln!("No notification with id {} in the local cache", id)
+            }
+        }
+        Some((command @ ("pin" | "unpin"), sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            if !set_pinned(store, id, command == "pin")? {
+                eprintln!("No notification with id {} in the local cache", id);
+            }
+        }
+        Some(("unsnooze", sub_matches)) => {
+            let id = sub_matches.get_one::<String>("ID").unwrap();
+            if !unsnooze(store, id)? {
+                eprintln!("No notification with id {} in the local cache", id);
+            }
+        }
+        Some(("sync", sub_matches)) => {
+            let mode = match (sub_matches.get_flag("backfill"), sub_matches.get_one::<String>("since")) {
+                (true, Some(since)) => parse_date(since).map(|since| SyncMode::Backfill { since: Some(since) }),
+                (true, None) => Ok(SyncMode::Backfill { since: None }),
+                (false, Some(_)) => Err(anyhow::anyhow!("--since only applies to --backfill")),
+                (false, None) => Ok(SyncMode::Incremental { reconcile: true }),
+            };
+            let result = mode.and_then(|mode| {
+                async_std::task::block_on(poll::sync_once(config, store, mode, |page, count| {
+                    eprintln!("Fetched page {}, {} notifications so far", page, count)
+                }))
+            });
+            match result {
+                Ok(synced) => println!(
+                    "Synced {} new and {} updated notifications, {} read elsewhere, {} dropped by rules",
+                    synced.new, synced.updated, synced.read_elsewhere, synced.dropped
+                ),
+                Err(err) => eprintln!("Failed to sync, {}", err),
+            }
+        }
+        Some(("export", sub_matches)) => {
+            let format = sub_matches
+                .get_one::<String>("format")
+                .unwrap()
+                .parse::<Format>()
+                .unwrap();
+            let result = sub_matches
+                .get_one::<String>("since")
+                .map(|since| parse_date(since))
+                .transpose()
+                .and_then(|since| export::export(store, format, since, &mut std::io::stdout().lock()));
+            match result {
+                Ok(count) => eprintln!("Exported {} notifications", count),
+                Err(err) => eprintln!("Failed to export, {}", err),
+            }
+        }
+        Some(("import", sub_matches)) => {
+            let file = PathBuf::from(sub_matches.get_one::<String>("FILE").unwrap());
+            let format = match sub_matches.get_one::<String>("format") {
+                Some(format) => format.parse::<Format>().unwrap(),
+                None => Format::from_path(&file).unwrap_or(Format::Json),
+            };
+            let mut input = String::new();
+            let read = if file.as_os_str() == "-" {
+                std::io::stdin().read_to_string(&mut input).map(|_| ())
+            } else {
+                std::fs::read_to_string(&file).map(|content| input = content)
+            };
+            let result = read
+                .map_err(|err| anyhow::anyhow!("could not read {}, {}", file.display(), err))
+                .and_then(|_| export::parse(format, &input))
+                .and_then(|records| export::import(store, &records));
+            match result {
+                Ok(count) => println!("Imported {} notifications", count),
+                Err(err) => eprintln!("Failed to import, {}", err),
+            }
+        }
+        Some(("status", _)) => print_status(store, config)?,
+        Some(("rules", sub_matches)) => match sub_matches.subcommand() {
+            Some(("test", _)) => print_rule_matches(store, config)?,
+            _ => unreachable!(),
+        },
+        Some(("prune", _)) => prune_all(store)?,
+        Some(("view", _)) => tui::terminal::open(store, config)?,
 
         _ => unreachable!(),
     };
+
+    Ok(())
 }
@@ -1,55 +1,180 @@
-use std::{collections::HashMap, fs, io::Write, path::PathBuf};
+use std::{collections::HashMap, env, ffi::OsString, fs, io::Write, path::PathBuf, str::FromStr};
 
 use opener::open;
 
+use crate::cache::retention::Retention;
+use crate::rules::Rules;
+use crate::schedule::{PollSchedule, WorkDays, WorkHours};
+
+/// What happens to a notification once it is opened in the browser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadPolicy {
+    /// Mark the thread as read on github and hide it locally
+    Remote,
+    /// Hide it locally, the thread stays unread on github
+    Local,
+    /// Leave the notification as it is
+    Unread,
+}
+
+impl FromStr for ReadPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.trim() {
+            "remote" => Ok(ReadPolicy::Remote),
+            "local" => Ok(ReadPolicy::Local),
+            "unread" => Ok(ReadPolicy::Unread),
+            other => anyhow::bail!(
+                "Unknown read policy `{}`, expected one of remote, local or unread",
+                other
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct AdditionalConfig {
     polling_interval_seconds: u32,
+    polling_max_interval_seconds: u32,
+    polling_off_hours_interval_seconds: u32,
+    polling_work_hours: Option<WorkHours>,
+    polling_work_days: WorkDays,
     polling_window_days: u32,
     enable_os_notifications: bool,
+    open_read_policy: ReadPolicy,
+    retention_read_days: u32,
+    retention_max_notifications: u32,
+    rules: Rules,
 }
 
 impl Default for AdditionalConfig {
     fn default() -> Self {
         Self {
             polling_interval_seconds: 60,
+            polling_max_interval_seconds: 300,
+            polling_off_hours_interval_seconds: 900,
+            polling_work_hours: None,
+            polling_work_days: WorkDays::default(),
             polling_window_days: 2,
             enable_os_notifications: true,
+            open_read_policy: ReadPolicy::Local,
+            retention_read_days: 30,
+            retention_max_notifications: 5000,
+            rules: Rules::default(),
         }
     }
 }
 
+/// Settings the configuration file may contain, anything else is rejected as a likely typo. `rule` may be repeated.
+const SETTINGS: &[&str] = &[
+    "polling_interval_seconds",
+    "polling_max_interval_seconds",
+    "polling_off_hours_interval_seconds",
+    "polling_work_hours",
+    "polling_work_days",
+    "polling_window_days",
+    "enable_os_notifications",
+    "open_read_policy",
+    "retention_read_days",
+    "retention_max_notifications",
+    "rule",
+];
+
+fn setting<T>(map: &HashMap<&str, &str>, key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match map.get(key) {
+        Some(value) => value
+            .parse::<T>()
+            .map_err(|err| anyhow::anyhow!("Invalid {} `{}`, {}", key, value, err)),
+        None => Ok(default),
+    }
+}
+
+fn positive(key: &str, value: u32) -> anyhow::Result<u32> {
+    if value == 0 {
+        anyhow::bail!("Invalid {} `0`, it must be at least 1", key);
+    }
+    Ok(value)
+}
+
 impl AdditionalConfig {
-    fn load(config_file: &PathBuf) -> AdditionalConfig {
-        let mut map = HashMap::new();
-        let config_content = fs::read_to_string(config_file).unwrap();
+    fn load(config_file: &PathBuf) -> anyhow::Result<AdditionalConfig> {
+        let config_content = fs::read_to_string(config_file)
+            .map_err(|err| anyhow::anyhow!("Could not read {}, error: {}", config_file.display(), err))?;
+        Self::parse(&config_content).map_err(|err| anyhow::anyhow!("{} in {}", err, config_file.display()))
+    }
 
-        for line in config_content.lines() {
-            if !line.contains('=') {
+    /// Parses `key=value` lines, lines starting with `//` or `#` are comments.
+    fn parse(config_content: &str) -> anyhow::Result<AdditionalConfig> {
+        let mut map = HashMap::new();
+        let mut rules = vec![];
+        for line in config_content.lines().map(str::trim) {
+            if line.starts_with("//") || line.starts_with('#') {
                 continue;
             }
-            let config = line.split('=').collect::<Vec<&str>>().to_owned();
-            map.insert(config[0].to_string(), config[1].to_string());
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            if !SETTINGS.contains(&key) {
+                anyhow::bail!("Unknown setting `{}`", key);
+            }
+            if key == "rule" {
+                rules.push(
+                    value
+                        .parse()
+                        .map_err(|err| anyhow::anyhow!("Invalid rule `{}`, {}", value, err))?,
+                );
+                continue;
+            }
+            map.insert(key, value);
         }
 
-        AdditionalConfig {
-            polling_interval_seconds: map
-                .get("polling_interval_seconds")
-                .unwrap_or(&"60".to_string())
-                .parse::<u32>()
-                .unwrap(),
-
-            polling_window_days: map
-                .get("polling_window_days")
-                .unwrap_or(&"2".to_string())
-                .parse::<u32>()
-                .unwrap(),
-
-            enable_os_notifications: map
-                .get("enable_os_notifications")
-                .unwrap_or(&"true".to_string())
-                .parse::<bool>()
-                .unwrap(),
-        }
+        let defaults = AdditionalConfig::default();
+        Ok(AdditionalConfig {
+            polling_interval_seconds: positive(
+                "polling_interval_seconds",
+                setting(&map, "polling_interval_seconds", defaults.polling_interval_seconds)?,
+            )?,
+            polling_max_interval_seconds: positive(
+                "polling_max_interval_seconds",
+                setting(
+                    &map,
+                    "polling_max_interval_seconds",
+                    defaults.polling_max_interval_seconds,
+                )?,
+            )?,
+            polling_off_hours_interval_seconds: positive(
+                "polling_off_hours_interval_seconds",
+                setting(
+                    &map,
+                    "polling_off_hours_interval_seconds",
+                    defaults.polling_off_hours_interval_seconds,
+                )?,
+            )?,
+            polling_work_hours: match map.get("polling_work_hours").filter(|hours| !hours.is_empty()) {
+                Some(hours) => Some(hours.parse::<WorkHours>()?),
+                None => None,
+            },
+            polling_work_days: setting(&map, "polling_work_days", defaults.polling_work_days)?,
+            polling_window_days: positive(
+                "polling_window_days",
+                setting(&map, "polling_window_days", defaults.polling_window_days)?,
+            )?,
+            enable_os_notifications: setting(&map, "enable_os_notifications", defaults.enable_os_notifications)?,
+            open_read_policy: setting(&map, "open_read_policy", defaults.open_read_policy)?,
+            retention_read_days: setting(&map, "retention_read_days", defaults.retention_read_days)?,
+            retention_max_notifications: setting(
+                &map,
+                "retention_max_notifications",
+                defaults.retention_max_notifications,
+            )?,
+            rules: Rules::new(rules),
+        })
     }
 
     pub fn get_polling_window_days(&self) -> u32 {
@@ -60,62 +185,177 @@ impl AdditionalConfig {
         self.polling_interval_seconds
     }
 
+    pub fn get_poll_schedule(&self) -> PollSchedule {
+        PollSchedule {
+            interval: chrono::Duration::seconds(self.polling_interval_seconds.into()),
+            max_interval: chrono::Duration::seconds(self.polling_max_interval_seconds.into()),
+            off_hours_interval: chrono::Duration::seconds(self.polling_off_hours_interval_seconds.into()),
+            work_hours: self.polling_work_hours,
+            work_days: self.polling_work_days,
+        }
+    }
+
     pub fn get_enable_os_notifications(&self) -> bool {
         self.enable_os_notifications
     }
+
+    pub fn get_open_read_policy(&self) -> ReadPolicy {
+        self.open_read_policy
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn get_retention(&self) -> Retention {
+        Retention {
+            read_days: self.retention_read_days,
+            max_notifications: self.retention_max_notifications,
+        }
+    }
+}
+
+/// Where ghostie keeps its files, settings and the token, the notification cache, and logs with the pid file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locations {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub state_dir: PathBuf,
 }
 
+impl Locations {
+    /// Everything in one directory, the layout of an explicit home and of the legacy `~/.ghostie`.
+    pub fn single(root: PathBuf) -> Self {
+        Self {
+            config_dir: root.clone(),
+            data_dir: root.clone(),
+            state_dir: root,
+        }
+    }
+
+    /// Resolves `--home`, then `$GHOSTIE_HOME`, then an existing `~/.ghostie`, then the XDG base directories.
+    /// A relative home is made absolute, the background process changes directory to `/` once started.
+    pub fn resolve<F>(home: Option<PathBuf>, user_home: Option<PathBuf>, var: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let var = |key: &str| var(key).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+        if let Some(root) = home.or_else(|| var("GHOSTIE_HOME")) {
+            return Ok(Self::single(env::current_dir()?.join(root)));
+        }
+
+        if let Some(legacy) = user_home.as_ref().map(|home| home.join(".ghostie")) {
+            if legacy.is_dir() {
+                return Ok(Self::single(legacy));
+            }
+        }
+
+        // The XDG specification says relative base directories are invalid and should be ignored
+        let base_dir = |key: &str, default: &str| {
+            var(key)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| user_home.as_ref().map(|home| home.join(default)))
+                .map(|dir| dir.join("ghostie"))
+                .ok_or_else(|| anyhow::anyhow!("Could not find a home directory, set GHOSTIE_HOME or pass --home"))
+        };
+
+        Ok(Self {
+            config_dir: base_dir("XDG_CONFIG_HOME", ".config")?,
+            data_dir: base_dir("XDG_DATA_HOME", ".local/share")?,
+            state_dir: base_dir("XDG_STATE_HOME", ".local/state")?,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub cache_file: PathBuf,
     pub token_file: PathBuf,
     pub config_file: PathBuf,
+    pub state_dir: PathBuf,
     pub additional_config: AdditionalConfig,
 }
 
-lazy_static::lazy_static! {
-   pub static ref CONFIG: Config = {
-        let home_dir = dirs::home_dir()
-            .ok_or_else(|| PathBuf::from("./"))
-            .unwrap()
-            .join(PathBuf::from(".ghostie"));
+impl Config {
+    /// Loads the configuration for the user's environment, `home` overrides every other location.
+    pub fn load(home: Option<PathBuf>) -> anyhow::Result<Self> {
+        Self::from_locations(Locations::resolve(home, dirs::home_dir(), |key| env::var_os(key))?)
+    }
 
-        if !home_dir.exists() {
-            std::fs::create_dir(home_dir.clone()).unwrap();
-        }
+    /// Like `load` with default settings, so `ghostie configure` can open a configuration file that doesn't parse.
+    pub fn load_defaults(home: Option<PathBuf>) -> anyhow::Result<Self> {
+        Self::with_defaults(Locations::resolve(home, dirs::home_dir(), |key| env::var_os(key))?)
+    }
 
-        let additional_config_path = home_dir.join("ghostie.config");
-        let mut additional_config = AdditionalConfig::default();
-        if additional_config_path.exists() {
-          additional_config = AdditionalConfig::load(&additional_config_path);
-        }
+    pub fn from_locations(locations: Locations) -> anyhow::Result<Self> {
+        let config = Self::with_defaults(locations)?;
+        config.reload()
+    }
 
-        Config {
-          cache_file: home_dir.join("notifications.db"),
-          token_file: home_dir.join("github.token"),
-          config_file: additional_config_path,
-          additional_config,
+    fn with_defaults(locations: Locations) -> anyhow::Result<Self> {
+        for dir in [&locations.config_dir, &locations.data_dir, &locations.state_dir] {
+            fs::create_dir_all(dir)
+                .map_err(|err| anyhow::anyhow!("Could not create {}, error: {}", dir.display(), err))?;
         }
-    };
-}
 
-impl Config {
-    pub fn read() -> &'static Self {
-        &CONFIG
+        Ok(Config {
+            cache_file: locations.data_dir.join("notifications.db"),
+            token_file: locations.config_dir.join("github.token"),
+            config_file: locations.config_dir.join("ghostie.config"),
+            state_dir: locations.state_dir,
+            additional_config: AdditionalConfig::default(),
+        })
     }
 
-    pub fn edit_additional_config() {
-        let config_file = &CONFIG.config_file;
+    /// Re-reads the configuration file, an invalid file is reported and leaves `self` as it is.
+    pub fn reload(&self) -> anyhow::Result<Self> {
+        let mut config = self.clone();
+        config.additional_config = if self.config_file.exists() {
+            AdditionalConfig::load(&self.config_file)?
+        } else {
+            AdditionalConfig::default()
+        };
+        Ok(config)
+    }
+
+    pub fn edit_additional_config(&self) {
+        let config_file = &self.config_file;
         if !config_file.exists() {
             Write::write_all(
                 &mut fs::File::create(config_file).unwrap(),
                 r#"// Frequency of polling notifications from Github in seconds
 polling_interval_seconds=60
 
+// Polling backs off step by step up to this many seconds while nothing new happens
+polling_max_interval_seconds=300
+
+// Work hours and days, e.g. 09:00-18:00 and mon-fri, outside them polling_off_hours_interval_seconds is used.
+// Leave polling_work_hours empty to poll the same way all week
+polling_work_hours=
+polling_work_days=mon-fri
+polling_off_hours_interval_seconds=900
+
 // Number of days to be used as polling window
 polling_window_days=2
 
 // OS specific notifications/alerts
-enable_os_notifications=true"#
+enable_os_notifications=true
+
+// What opening a notification does: remote (mark read on github), local (hide locally) or unread
+open_read_policy=local
+
+// Days read notifications stay in the local cache, unread ones are kept until read
+retention_read_days=30
+
+// Upper bound on cached notifications, the oldest read ones are pruned first
+retention_max_notifications=5000
+
+// Rules run on every new or updated notification, `matchers -> actions`, e.g.
+//   rule=author:dependabot* -> read tag:deps
+//   rule=org:my-company reason:review_requested -> priority
+// Matchers: repo:<owner/name glob>, org:<owner glob>, reason:<reason>, type:<subject type>, title:<regex, "quoted" with spaces>, author:<glob>
+// Actions: drop, mute, read, done, tag:<name>, priority. Check them with `ghostie rules test`"#
                     .to_string()
                     .as_bytes(),
             )
@@ -125,3 +365,105 @@ enable_os_notifications=true"#
         open(config_file).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::{AdditionalConfig, Locations, ReadPolicy};
+
+    fn a_user_home(name: &str) -> PathBuf {
+        let home = std::env::temp_dir().join(format!("ghostie-home-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&home).ok();
+        std::fs::create_dir_all(&home).unwrap();
+        home
+    }
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<OsString> {
+        move |key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+
+    #[test]
+    fn parses_settings() {
+        let config = AdditionalConfig::parse(
+            "// Frequency of polling\npolling_interval_seconds = 30\n# a comment\nopen_read_policy=remote\npolling_work_hours=\n\
+            rule=author:dependabot* -> read\nrule = reason:mention -> priority\n",
+        )
+        .unwrap();
+        assert_eq!(config.get_polling_interval_seconds(), 30);
+        assert_eq!(config.get_open_read_policy(), ReadPolicy::Remote);
+        assert_eq!(config.get_poll_schedule().work_hours, None);
+        assert_eq!(config.get_polling_window_days(), 2);
+        assert_eq!(config.get_rules().iter().count(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        for content in [
+            "polling_interval_seconds=soon",
+            "polling_interval_seconds=0",
+            "open_read_policy=sometimes",
+            "rule=repo:octocat/* -> archive",
+            "polling_work_hours=9 to 5",
+            "poling_interval_seconds=60",
+        ] {
+            assert!(AdditionalConfig::parse(content).is_err(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn explicit_home_wins() {
+        let home = a_user_home("explicit");
+        let vars = env(&[("GHOSTIE_HOME", "/tmp/from-env")]);
+
+        let locations = Locations::resolve(Some(PathBuf::from("/tmp/from-flag")), Some(home.clone()), &vars).unwrap();
+        assert_eq!(locations, Locations::single(PathBuf::from("/tmp/from-flag")));
+
+        let locations = Locations::resolve(None, Some(home), &vars).unwrap();
+        assert_eq!(locations, Locations::single(PathBuf::from("/tmp/from-env")));
+    }
+
+    #[test]
+    fn makes_a_relative_home_absolute() {
+        let home = a_user_home("relative");
+        let cwd = std::env::current_dir().unwrap();
+
+        let locations = Locations::resolve(Some(PathBuf::from("./from-flag")), Some(home.clone()), env(&[])).unwrap();
+        assert_eq!(locations, Locations::single(cwd.join("./from-flag")));
+        assert!(locations.state_dir.is_absolute());
+
+        let locations = Locations::resolve(None, Some(home.clone()), env(&[("GHOSTIE_HOME", "from-env")])).unwrap();
+        assert_eq!(locations, Locations::single(cwd.join("from-env")));
+
+        let locations = Locations::resolve(None, Some(home.clone()), env(&[("XDG_STATE_HOME", "relative")])).unwrap();
+        assert_eq!(locations.state_dir, home.join(".local/state/ghostie"));
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn keeps_using_legacy_home() {
+        let home = a_user_home("legacy");
+        std::fs::create_dir(home.join(".ghostie")).unwrap();
+
+        let locations = Locations::resolve(None, Some(home.clone()), env(&[("XDG_CONFIG_HOME", "/xdg")])).unwrap();
+        assert_eq!(locations, Locations::single(home.join(".ghostie")));
+        std::fs::remove_dir_all(&home).ok();
+    }
+
+    #[test]
+    fn defaults_to_xdg_directories() {
+        let home = a_user_home("xdg");
+
+        let locations = Locations::resolve(None, Some(home.clone()), env(&[("XDG_DATA_HOME", "/xdg/data")])).unwrap();
+        assert_eq!(locations.config_dir, home.join(".config/ghostie"));
+        assert_eq!(locations.data_dir, PathBuf::from("/xdg/data/ghostie"));
+        assert_eq!(locations.state_dir, home.join(".local/state/ghostie"));
+
+        assert!(Locations::resolve(None, None, env(&[])).is_err());
+        std::fs::remove_dir_all(&home).ok();
+    }
+}
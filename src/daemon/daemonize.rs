@@ -1,20 +1,21 @@
 use async_std::task;
 use daemonize::Daemonize;
 
-use crate::daemon::proc::ProcManager;
+use crate::configuration::config::Config;
+use crate::daemon::{proc::ProcManager, signals::Wake};
 use crate::log::Logger;
 use crate::{error, info};
 
 pub struct Daemon;
 
 impl Daemon {
-    pub fn daemonize<Fut>(async_process: impl Fn() -> Fut)
+    pub fn daemonize<Fut>(config: &Config, async_process: impl Fn() -> Fut)
     where
         Fut: std::future::Future<Output = ()>,
     {
-        let logger = Logger::init("ghostie_daemon");
+        let logger = Logger::init(&config.state_dir, "ghostie_daemon");
 
-        match ProcManager::init("ghostie").register() {
+        match ProcManager::init(&config.state_dir, "ghostie").register() {
             Ok(proc) => {
                 Self::run_as_daemon(proc, logger);
                 task::block_on(async {
@@ -26,17 +27,28 @@ impl Daemon {
         }
     }
 
-    pub fn stop_daemon() {
-        ProcManager::init("ghostie").kill_process().unwrap();
+    pub fn stop_daemon(config: &Config) {
+        ProcManager::init(&config.state_dir, "ghostie").kill_process().unwrap();
         info!("ghostie background stopped by user")
     }
 
-    pub fn show_logs() {
-        Logger::init("ghostie_daemon").display_stdout();
+    pub fn running_pid(config: &Config) -> Option<i32> {
+        ProcManager::init(&config.state_dir, "ghostie").running_pid()
     }
 
-    pub fn clear_logs() {
-        Logger::init("ghostie_daemon").clear_stdout().unwrap();
+    /// Wakes the background process up, returning whether it is running.
+    pub fn wake(config: &Config, wake: Wake) -> anyhow::Result<bool> {
+        ProcManager::init(&config.state_dir, "ghostie").signal(wake.signal())
+    }
+
+    pub fn show_logs(config: &Config) {
+        Logger::init(&config.state_dir, "ghostie_daemon").display_stdout();
+    }
+
+    pub fn clear_logs(config: &Config) {
+        Logger::init(&config.state_dir, "ghostie_daemon")
+            .clear_stdout()
+            .unwrap();
     }
 
     fn run_as_daemon(proc: ProcManager, logger: Logger) {
@@ -1,2 +1,3 @@
 pub mod daemonize;
 mod proc;
+pub mod signals;
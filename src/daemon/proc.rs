@@ -1,65 +1,102 @@
-use std::fs;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
-use sysinfo::{Pid, ProcessExt, System, SystemExt};
+use sysinfo::{Pid, ProcessExt, Signal, System, SystemExt};
 
 pub struct ProcManager {
-    pub pid_file: String,
+    pub pid_file: PathBuf,
+    /// Where builds before the state directory kept the pid, so a background process started by one is still found
+    legacy_pid_file: PathBuf,
+    process_name: String,
 }
 
 impl ProcManager {
-    pub fn init(process_name: &str) -> Self {
+    pub fn init(state_dir: &Path, process_name: &str) -> Self {
         Self {
-            pid_file: format!("/tmp/{}_pid_file", process_name),
+            pid_file: state_dir.join(format!("{}.pid", process_name)),
+            legacy_pid_file: PathBuf::from(format!("/tmp/{}_pid_file", process_name)),
+            process_name: process_name.to_owned(),
         }
     }
 
+    fn pid_files(&self) -> [&Path; 2] {
+        [&self.pid_file, &self.legacy_pid_file]
+    }
+
     pub fn register(self) -> Result<Self> {
         Self::try_setup(&self)?;
         Ok(self)
     }
 
     pub fn kill_process(&self) -> Result<()> {
-        let pid = self.read_pid_from_file();
-        if let Some(process_id) = pid {
-            if let Some(proc) = System::new_all().process(Pid::from(process_id)) {
-                proc.kill();
-                fs::remove_file(&self.pid_file)?;
+        for pid_file in self.pid_files() {
+            if let Some(process_id) = Self::read_pid_from_file(pid_file) {
+                if !self.check_existing_process(Some(process_id)) {
+                    continue;
+                }
+                if let Some(proc) = System::new_all().process(Pid::from(process_id)) {
+                    proc.kill();
+                    fs::remove_file(pid_file)?;
+                }
             }
         }
 
         Ok(())
     }
 
+    /// The pid of the background process, if it is running.
+    pub fn running_pid(&self) -> Option<i32> {
+        self.pid_files()
+            .into_iter()
+            .map(Self::read_pid_from_file)
+            .find(|pid| self.check_existing_process(*pid))
+            .flatten()
+    }
+
+    /// Sends `signal` to the background process, returning whether it is running.
+    pub fn signal(&self, signal: Signal) -> Result<bool> {
+        let pid = match self.running_pid() {
+            Some(pid) => pid,
+            None => return Ok(false),
+        };
+        match System::new_all()
+            .process(Pid::from(pid))
+            .and_then(|proc| proc.kill_with(signal))
+        {
+            Some(true) => Ok(true),
+            Some(false) => anyhow::bail!("Could not signal the background process, process id: {}", pid),
+            None => anyhow::bail!("{:?} is not supported on this platform", signal),
+        }
+    }
+
     fn try_setup(&self) -> Result<()> {
-        let pid = self.read_pid_from_file();
-        let status = Self::check_existing_process(pid);
-        if status {
-            anyhow::bail!(
-                "The background process is already running, process id: {}",
-                pid.unwrap()
-            );
+        if let Some(pid) = self.running_pid() {
+            anyhow::bail!("The background process is already running, process id: {}", pid);
         }
 
-        if !status && self.read_pid_from_file().is_some() {
+        if Self::read_pid_from_file(&self.pid_file).is_some() {
             fs::remove_file(&self.pid_file)?;
         }
 
         Ok(())
     }
 
-    fn check_existing_process(pid: Option<i32>) -> bool {
+    /// Whether `pid` is a running ghostie, a stale pid file may name a process id reused by another program.
+    fn check_existing_process(&self, pid: Option<i32>) -> bool {
         if let Some(process_id) = pid {
-            if System::new_all().process(Pid::from(process_id)).is_some() {
-                return true;
+            if let Some(proc) = System::new_all().process(Pid::from(process_id)) {
+                return proc.name().contains(&self.process_name);
             }
         }
 
         false
     }
 
-    fn read_pid_from_file(&self) -> Option<i32> {
-        if let Ok(pid) = fs::read_to_string(&self.pid_file) {
+    fn read_pid_from_file(pid_file: &Path) -> Option<i32> {
+        if let Ok(pid) = fs::read_to_string(pid_file) {
             if !pid.is_empty() {
                 return Some(pid.parse::<i32>().unwrap());
             }
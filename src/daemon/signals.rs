@@ -0,0 +1,48 @@
+use async_std::channel::{unbounded, Receiver};
+use signal_hook::{
+    consts::{SIGHUP, SIGUSR1},
+    iterator::Signals,
+};
+use sysinfo::Signal;
+
+/// Why the background process woke up before its next poll was due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wake {
+    /// `ghostie refresh` or the TUI asked for a poll now
+    Refresh,
+    /// `ghostie reload` or a plain SIGHUP asked to re-read the configuration file
+    Reload,
+}
+
+impl Wake {
+    /// The signal other ghostie processes send to the background process for this wake up.
+    pub fn signal(self) -> Signal {
+        match self {
+            Wake::Refresh => Signal::User1,
+            Wake::Reload => Signal::Hangup,
+        }
+    }
+
+    fn from_signal(signal: i32) -> Option<Self> {
+        match signal {
+            SIGUSR1 => Some(Wake::Refresh),
+            SIGHUP => Some(Wake::Reload),
+            _ => None,
+        }
+    }
+}
+
+/// Forwards wake up signals to the returned channel from a dedicated thread, so the scheduler can sleep on it.
+pub fn listen() -> anyhow::Result<Receiver<Wake>> {
+    let mut signals = Signals::new([SIGUSR1, SIGHUP])?;
+    let (sender, receiver) = unbounded();
+    std::thread::spawn(move || {
+        for wake in signals.forever().filter_map(Wake::from_signal) {
+            if sender.send_blocking(wake).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(receiver)
+}
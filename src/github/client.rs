@@ -1,7 +1,7 @@
 use anyhow::Result;
 use futures::Future;
 use reqwest::{
-    header::{ACCEPT, AUTHORIZATION, USER_AGENT},
+    header::{HeaderMap, ACCEPT, AUTHORIZATION, USER_AGENT},
     Client, Method,
 };
 use serde::de::DeserializeOwned;
@@ -75,18 +75,44 @@ impl Github {
     }
 
     pub async fn patch<P: Serialize + ?Sized>(&self, uri: &str, params: Option<&P>) -> Result<()> {
-        self.request(Method::PATCH, self.host.clone() + uri, params).await?;
+        self.request(Method::PATCH, self.host.clone() + uri, params)
+            .await?
+            .error_for_status()?;
         Ok(())
     }
 
+    pub async fn delete(&self, uri: &str) -> Result<()> {
+        self.request(Method::DELETE, self.host.clone() + uri, None::<&()>)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Like `get` for a full API url, e.g. the subject url of a notification.
+    pub async fn get_url<T: DeserializeOwned>(&self, url: &str) -> Result<T, NotificationError> {
+        let response = self.request(Method::GET, url.to_owned(), None::<&()>).await?;
+        Ok(response.error_for_status()?.json::<T>().await?)
+    }
+
     pub async fn get<T: DeserializeOwned, P: Serialize + ?Sized>(
         &self,
         uri: &str,
         params: Option<&P>,
     ) -> Result<T, NotificationError> {
+        let (result, _) = self.get_with_headers(uri, params).await?;
+        Ok(result)
+    }
+
+    /// Like `get`, also returning the response headers, e.g. the server `Date`.
+    pub async fn get_with_headers<T: DeserializeOwned, P: Serialize + ?Sized>(
+        &self,
+        uri: &str,
+        params: Option<&P>,
+    ) -> Result<(T, HeaderMap), NotificationError> {
         let response = self.request(Method::GET, self.host.clone() + uri, params).await?;
+        let headers = response.headers().clone();
         let result = response.json::<T>().await?;
-        Ok(result)
+        Ok((result, headers))
     }
 
     pub fn user_activity(&self) -> Activity {
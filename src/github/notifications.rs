@@ -1,9 +1,50 @@
 use crate::github::client::{Github, NotificationError};
 
+use reqwest::header::{HeaderMap, DATE, LINK};
 use serde::Deserialize;
 
 type DateTime = chrono::DateTime<chrono::Utc>;
 
+/// The most github returns per page of notifications.
+pub const MAX_PER_PAGE: u32 = 50;
+
+/// Notifications along with the time github served them at, according to its `Date` header.
+pub struct NotificationsPage {
+    pub notifications: Vec<Notification>,
+    pub server_date: Option<DateTime>,
+    /// The page to ask for next, from the `rel="next"` entry of the `Link` header
+    pub next_page: Option<u32>,
+    /// The fewest seconds github wants between polls, from the `X-Poll-Interval` header
+    pub poll_interval: Option<u32>,
+}
+
+fn server_date(headers: &HeaderMap) -> Option<DateTime> {
+    let date = headers.get(DATE)?.to_str().ok()?;
+    chrono::DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.with_timezone(&chrono::Utc))
+}
+
+fn poll_interval(headers: &HeaderMap) -> Option<u32> {
+    headers.get("x-poll-interval")?.to_str().ok()?.trim().parse().ok()
+}
+
+fn next_page(headers: &HeaderMap) -> Option<u32> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    let next = link.split(',').find(|entry| entry.contains("rel=\"next\""))?;
+    let url = next
+        .split(';')
+        .next()?
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    let query = url.split_once('?')?.1;
+    query
+        .split('&')
+        .find_map(|param| param.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+}
+
 pub struct Notifications {
     github: Github,
 }
@@ -13,9 +54,16 @@ impl Notifications {
         Self { github }
     }
 
-    pub fn builder(&self) -> NotificationsBuilder {
+    pub fn builder(&self) -> NotificationsBuilder<'_> {
         NotificationsBuilder::new(&self.github)
     }
+
+    /// Who opened the subject at `subject_url`, the `user` of issues and pull requests or the `author` of releases
+    /// and commits.
+    pub async fn subject_author(&self, subject_url: &str) -> Result<Option<String>, NotificationError> {
+        let details: SubjectDetails = self.github.get_url(subject_url).await?;
+        Ok(details.user.or(details.author).map(|owner| owner.login))
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -26,6 +74,10 @@ pub struct NotificationsBuilder<'client> {
     since: Option<DateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     before: Option<DateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_page: Option<u32>,
 }
 
 impl<'client> NotificationsBuilder<'client> {
@@ -34,6 +86,8 @@ impl<'client> NotificationsBuilder<'client> {
             github,
             since: None,
             before: None,
+            page: None,
+            per_page: None,
         }
     }
 
@@ -47,10 +101,28 @@ impl<'client> NotificationsBuilder<'client> {
         self
     }
 
-    pub async fn list(self) -> Result<Vec<Notification>, NotificationError> {
-        self.github
-            .get::<Vec<Notification>, NotificationsBuilder>("notifications", Some(&self))
-            .await
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.per_page = Some(per_page);
+        self
+    }
+
+    pub async fn list(self) -> Result<NotificationsPage, NotificationError> {
+        let (notifications, headers) = self
+            .github
+            .get_with_headers::<Vec<Notification>, NotificationsBuilder>("notifications", Some(&self))
+            .await?;
+
+        Ok(NotificationsPage {
+            notifications,
+            server_date: server_date(&headers),
+            next_page: next_page(&headers),
+            poll_interval: poll_interval(&headers),
+        })
     }
 
     pub async fn mark_as_read(self, notification_id: &str) -> anyhow::Result<()> {
@@ -60,6 +132,12 @@ impl<'client> NotificationsBuilder<'client> {
 
         Ok(())
     }
+
+    pub async fn mark_as_done(self, notification_id: &str) -> anyhow::Result<()> {
+        self.github
+            .delete(&format!("notifications/threads/{}", notification_id))
+            .await
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,4 +168,65 @@ pub struct Repository {
     pub name: String,
     pub full_name: String,
     pub html_url: String,
+    pub owner: Owner,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubjectDetails {
+    user: Option<Owner>,
+    author: Option<Owner>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Owner {
+    pub login: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue, DATE, LINK};
+
+    use super::{next_page, poll_interval, server_date};
+
+    #[test]
+    fn parses_the_date_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(server_date(&headers), None);
+
+        headers.insert(DATE, HeaderValue::from_static("Mon, 12 Dec 2022 18:52:24 GMT"));
+        assert_eq!(
+            server_date(&headers).map(|date| date.to_rfc3339()),
+            Some(String::from("2022-12-12T18:52:24+00:00"))
+        );
+    }
+
+    #[test]
+    fn reads_the_poll_interval() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(poll_interval(&headers), None);
+
+        headers.insert("X-Poll-Interval", HeaderValue::from_static("60"));
+        assert_eq!(poll_interval(&headers), Some(60));
+    }
+
+    #[test]
+    fn follows_the_next_link() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(next_page(&headers), None);
+
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                "<https://api.github.com/notifications?per_page=50&page=3>; rel=\"next\", \
+                <https://api.github.com/notifications?per_page=50&page=7>; rel=\"last\"",
+            ),
+        );
+        assert_eq!(next_page(&headers), Some(3));
+
+        headers.insert(
+            LINK,
+            HeaderValue::from_static("<https://api.github.com/notifications?page=1>; rel=\"first\""),
+        );
+        assert_eq!(next_page(&headers), None);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod actions;
 pub mod cache;
 pub mod cli;
 pub mod configuration;
@@ -6,4 +7,7 @@ pub mod github;
 pub mod log;
 pub mod platform;
 pub mod poll;
+pub mod rules;
+pub mod schedule;
+pub mod snooze;
 pub mod tui;
@@ -1,18 +1,21 @@
-use std::fs::{self, File};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 use regex::Regex;
 
 pub struct Logger {
-    pub stdout_file: String,
-    pub stderr_file: String,
+    pub stdout_file: PathBuf,
+    pub stderr_file: PathBuf,
 }
 
 impl Logger {
-    pub fn init(file_name: &str) -> Self {
+    pub fn init(log_dir: &Path, file_name: &str) -> Self {
         Self {
-            stdout_file: format!("/tmp/{}.out", file_name),
-            stderr_file: format!("/tmp/{}.err", file_name),
+            stdout_file: log_dir.join(format!("{}.out", file_name)),
+            stderr_file: log_dir.join(format!("{}.err", file_name)),
         }
     }
 
@@ -23,7 +26,7 @@ impl Logger {
         Ok([stdout, stderr])
     }
 
-    fn read_file(file: &str) -> String {
+    fn read_file(file: &Path) -> String {
         fs::read_to_string(file).unwrap_or_default()
     }
 
@@ -58,7 +61,7 @@ impl Logger {
                 (chrono::offset::Utc::now(), s)
             })
             .collect();
-        logs.sort_by(|a, b| a.0.cmp(&b.0));
+        logs.sort_by_key(|log| log.0);
         for log in logs.iter() {
             println!("{}", log.1)
         }
@@ -2,9 +2,12 @@ use std::process::exit;
 
 use dialoguer::{theme::ColorfulTheme, Confirm, Password};
 
-use ghostie::{cli, configuration::token::Token};
+use ghostie::{
+    cli,
+    configuration::{config::Config, token::Token},
+};
 
-fn prompt_token_flow() {
+fn prompt_token_flow(config: &Config) {
     let github_token = Password::with_theme(&ColorfulTheme::default())
         .with_prompt("Github token (no bearer/token prefix)")
         .allow_empty_password(false)
@@ -18,8 +21,8 @@ fn prompt_token_flow() {
         .unwrap()
     {
         Some(true) => {
-            Token::set(github_token);
-            println!("Successfully persisted token in ~/.ghostie/github.token")
+            Token::set(config, github_token);
+            println!("Successfully persisted token in {}", config.token_file.display())
         }
         Some(false) => {
             eprintln!("ghostie requires the token to be persisted :(");
@@ -30,9 +33,20 @@ fn prompt_token_flow() {
 }
 
 fn main() {
-    if !Token::is_set() {
-        prompt_token_flow();
+    let matches = cli::command().get_matches();
+    let config = match matches.subcommand_name() {
+        // Fixing an invalid configuration file is what configure is for, don't refuse to open it
+        Some("configure") => Config::load_defaults(cli::home(&matches)),
+        _ => Config::load(cli::home(&matches)),
+    };
+    let config = config.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+
+    if !Token::is_set(&config) {
+        prompt_token_flow(&config);
     }
 
-    cli::init();
+    cli::init(&config, &matches);
 }
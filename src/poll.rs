@@ -1,97 +1,922 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
-use clokwerk::*;
+use async_std::channel::Receiver;
 
 use crate::{
-    cache::{notifications::Notification, Cache},
+    cache::{
+        actions::{PendingAction, RemoteAction},
+        authors::SubjectAuthor,
+        notifications::{timestamp, Notification},
+        recovery::RECOVERED_AT,
+        store::NotificationStore,
+        sync::{parse_timestamp, PollHealth, SyncCursor},
+        Cache,
+    },
     configuration::{config::Config, token::Token},
-    github::client::{Credentials, Github},
+    daemon::signals::{self, Wake},
+    github::{
+        client::{Credentials, Github},
+        notifications::MAX_PER_PAGE,
+    },
     platform,
+    rules::{Rules, Verdict},
 };
 
-use crate::{error, info};
+use crate::{error, info, warn};
+
+fn github_instance(config: &Config) -> Github {
+    Github::init_with_token(Credentials::Token(Token::get(config)))
+}
+
+/// Seconds re-fetched before the sync cursor, so threads updated while the last poll was in flight aren't missed.
+const SYNC_OVERLAP_SECONDS: i64 = 120;
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+/// How often a sync also fetches every thread still unread here to find those read elsewhere, other syncs only fetch
+/// from the sync cursor.
+const RECONCILE_INTERVAL_MINUTES: i64 = 60;
+
+/// Recorded after each sync that reconciled threads read elsewhere.
+const RECONCILED_AT: &str = "reconciled_at";
+
+/// What a sync fetches.
+pub enum SyncMode {
+    /// Threads updated since the last sync, and every thread unread here when `reconcile` is set or the last reconcile
+    /// is an interval old
+    Incremental { reconcile: bool },
+    /// Every unread thread, or those updated since the given date
+    Backfill { since: Option<DateTime> },
+}
+
+fn rolling_window(config: &Config) -> DateTime {
+    chrono::Utc::now() - chrono::Duration::days(config.additional_config.get_polling_window_days().into())
+}
+
+/// Fetches from the sync cursor, or from the rolling window on the first poll and when the cursor is older than it.
+fn sync_since(cursor: Option<&SyncCursor>, window: DateTime) -> DateTime {
+    match cursor {
+        Some(cursor) => (cursor.since() - chrono::Duration::seconds(SYNC_OVERLAP_SECONDS)).max(window),
+        None => window,
+    }
+}
+
+/// Like `sync_since`, but every unread thread is fetched again when the cache was recovered after the last sync.
+fn poll_since(cursor: Option<&SyncCursor>, recovered_at: Option<DateTime>, window: DateTime) -> Option<DateTime> {
+    match (cursor, recovered_at) {
+        (Some(cursor), Some(recovered_at)) if cursor.last_sync < recovered_at => None,
+        (None, Some(_)) => None,
+        _ => Some(sync_since(cursor, window)),
+    }
+}
 
-fn github_instance() -> Github {
-    Github::init_with_token(Credentials::Token(Token::get()))
+fn read_recovered_at(store: &dyn NotificationStore) -> anyhow::Result<Option<DateTime>> {
+    Ok(store.read_state(RECOVERED_AT)?.as_deref().and_then(parse_timestamp))
 }
 
-fn rolling_window() -> chrono::DateTime<chrono::Utc> {
-    chrono::Utc::now() - chrono::Duration::days(Config::read().additional_config.get_polling_window_days().into())
+fn read_reconciled_at(store: &dyn NotificationStore) -> anyhow::Result<Option<DateTime>> {
+    Ok(store.read_state(RECONCILED_AT)?.as_deref().and_then(parse_timestamp))
 }
 
-pub async fn mark_notification_as_read(notifcation_id: &str) {
-    github_instance()
+/// Whether it is time to reconcile again, going back to the oldest unread thread on every poll would defeat the cursor.
+fn reconcile_due(reconciled_at: Option<DateTime>, now: DateTime) -> bool {
+    reconciled_at
+        .is_none_or(|reconciled_at| now - reconciled_at >= chrono::Duration::minutes(RECONCILE_INTERVAL_MINUTES))
+}
+
+/// Cached threads still unread here, the only ones reading elsewhere can change.
+fn unread_here(cached: Vec<Notification>) -> Vec<Notification> {
+    cached
+        .into_iter()
+        .filter(|notification| notification.unread && notification.dismissed_at.is_none())
+        .collect()
+}
+
+/// Goes back far enough to fetch every thread still unread here, github only lists unread threads so the ones
+/// missing from the fetch were read elsewhere.
+fn reconcile_since(since: Option<DateTime>, unread: &[Notification]) -> Option<DateTime> {
+    let oldest = unread
+        .iter()
+        .filter_map(|notification| parse_timestamp(&notification.updated_at))
+        .min()
+        .map(|oldest| oldest - chrono::Duration::seconds(SYNC_OVERLAP_SECONDS));
+    match (since, oldest) {
+        (Some(since), Some(oldest)) => Some(since.min(oldest)),
+        (since, _) => since,
+    }
+}
+
+/// Threads unread here that github didn't list as unread when fetching every unread thread since `since`.
+fn read_elsewhere(unread: &[Notification], fetched: &[Notification], since: Option<DateTime>) -> Vec<String> {
+    let fetched: HashSet<&str> = fetched.iter().map(|notification| notification.id.as_str()).collect();
+    unread
+        .iter()
+        .filter(|notification| !fetched.contains(notification.id.as_str()))
+        .filter(|notification| match since {
+            Some(since) => parse_timestamp(&notification.updated_at).is_some_and(|updated_at| updated_at > since),
+            None => true,
+        })
+        .map(|notification| notification.id.clone())
+        .collect()
+}
+
+pub async fn mark_notification_as_read(config: &Config, notifcation_id: &str) -> anyhow::Result<()> {
+    github_instance(config)
         .user_activity()
         .notifications()
         .builder()
         .mark_as_read(notifcation_id)
         .await
-        .unwrap();
 }
 
-async fn fetch_notifications() -> Vec<Notification> {
-    let notifications: Vec<Notification> = github_instance()
+pub async fn mark_notification_as_done(config: &Config, notifcation_id: &str) -> anyhow::Result<()> {
+    github_instance(config)
         .user_activity()
         .notifications()
         .builder()
-        .since(rolling_window())
-        .list()
+        .mark_as_done(notifcation_id)
         .await
-        .unwrap_or_else(|error| {
-            error!("\n");
-            panic!("Failed to fetch notifications, {}", error)
-        })
+}
+
+/// Replays queued remote actions against github, the store is only touched once all requests are done.
+async fn replay_pending_actions(
+    config: &Config,
+    pending_actions: Vec<PendingAction>,
+) -> Vec<(PendingAction, anyhow::Result<()>)> {
+    let mut results = vec![];
+    for pending in pending_actions.into_iter() {
+        let result = match pending.action {
+            RemoteAction::MarkAsRead => mark_notification_as_read(config, &pending.thread_id).await,
+            RemoteAction::MarkAsDone => mark_notification_as_done(config, &pending.thread_id).await,
+        };
+        results.push((pending, result));
+    }
+
+    results
+}
+
+/// Failed actions stay queued with their error and are retried on the next poll.
+fn record_replayed_actions(
+    store: &dyn NotificationStore,
+    results: Vec<(PendingAction, anyhow::Result<()>)>,
+) -> anyhow::Result<()> {
+    for (pending, result) in results.into_iter() {
+        match result {
+            Ok(_) => store.complete_action(pending.id)?,
+            Err(err) => {
+                warn!(format!(
+                    "Failed to {} thread {} (attempt {}), {}",
+                    pending.action,
+                    pending.thread_id,
+                    pending.attempts + 1,
+                    err
+                ));
+                store.fail_action(pending.id, &err.to_string())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Notifications fetched from github with what it said about the first page.
+struct Fetched {
+    notifications: Vec<Notification>,
+    /// The earliest safe sync cursor
+    server_date: Option<DateTime>,
+    /// The fewest seconds github wants between polls
+    poll_interval: Option<u32>,
+    /// API urls of the issue, pull request or release behind each thread, by thread id
+    subject_urls: HashMap<String, String>,
+}
+
+/// Pages through unread notifications updated since `since`, or all of them, reporting progress after each page.
+async fn fetch_notifications<F: FnMut(u32, usize)>(
+    config: &Config,
+    since: Option<DateTime>,
+    mut progress: F,
+) -> anyhow::Result<Fetched> {
+    let notifications_api = github_instance(config).user_activity().notifications();
+    let mut notifications: Vec<Notification> = vec![];
+    let mut server_date = None;
+    let mut poll_interval = None;
+    let mut subject_urls = HashMap::new();
+    let mut page = Some(1);
+    while let Some(number) = page {
+        let mut builder = notifications_api.builder().page(number).per_page(MAX_PER_PAGE);
+        if let Some(since) = since {
+            builder = builder.since(since);
+        }
+        let fetched = builder.list().await?;
+
+        server_date = server_date.or(fetched.server_date);
+        poll_interval = poll_interval.or(fetched.poll_interval);
+        for notification in fetched.notifications.into_iter() {
+            if let Some(url) = notification.subject.url.clone() {
+                subject_urls.insert(notification.id.clone(), url);
+            }
+            notifications.push(Notification::from(notification));
+        }
+        progress(number, notifications.len());
+        page = fetched.next_page.filter(|next| *next > number);
+    }
+
+    Ok(Fetched {
+        notifications,
+        server_date,
+        poll_interval,
+        subject_urls,
+    })
+}
+
+/// Splits fetched notifications into brand new threads and cached threads with activity since they were cached,
+/// which keep their cached author. Uncached threads last updated before `cursor` were dropped by a rule when first
+/// fetched, they are only fetched again to reconcile older unread threads.
+fn changed_notifications(
+    fetched: Vec<Notification>,
+    store: &dyn NotificationStore,
+    cursor: Option<DateTime>,
+) -> anyhow::Result<(Vec<Notification>, Vec<Notification>)> {
+    let mut new_notifications = vec![];
+    let mut updated_notifications = vec![];
+    for mut notification in fetched.into_iter() {
+        match store.read_by_id(&notification.id)? {
+            None => {
+                let dropped_before = cursor.is_some_and(|cursor| {
+                    parse_timestamp(&notification.updated_at).is_some_and(|updated_at| updated_at < cursor)
+                });
+                if !dropped_before {
+                    new_notifications.push(notification);
+                }
+            }
+            Some(cached) if cached.updated_at != notification.updated_at => {
+                notification.author = cached.author;
+                updated_notifications.push(notification);
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok((new_notifications, updated_notifications))
+}
+
+/// Fills in authors already looked up for the subject of each thread, returning the threads still to look up with
+/// their subject url. An author that couldn't be looked up is only tried again once the thread has new activity.
+fn cached_authors(
+    store: &dyn NotificationStore,
+    notifications: &mut [Notification],
+    subject_urls: &HashMap<String, String>,
+) -> anyhow::Result<Vec<(usize, String)>> {
+    let mut missing = vec![];
+    for (index, notification) in notifications.iter_mut().enumerate() {
+        let url = match subject_urls.get(&notification.id) {
+            Some(url) if notification.author.is_none() => url,
+            _ => continue,
+        };
+        match store.read_subject_author(url)? {
+            Some(SubjectAuthor {
+                author: Some(author), ..
+            }) => notification.author = Some(author),
+            Some(unknown) if unknown.updated_at == notification.updated_at => {}
+            _ => missing.push((index, url.clone())),
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Remembers a lookup for the subject, a failed one is logged and the thread matched and cached without an author.
+fn record_author<E: std::fmt::Display>(
+    store: &dyn NotificationStore,
+    notification: &mut Notification,
+    subject_url: &str,
+    looked_up: Result<Option<String>, E>,
+) -> anyhow::Result<()> {
+    notification.author = looked_up.unwrap_or_else(|err| {
+        warn!(format!(
+            "Failed to fetch the author of thread {}, {}",
+            notification.id, err
+        ));
+        None
+    });
+    store.write_subject_author(
+        subject_url,
+        &SubjectAuthor {
+            author: notification.author.clone(),
+            updated_at: notification.updated_at.clone(),
+        },
+    )
+}
+
+/// Looks up who opened the subject of each thread without a known author, once per subject.
+async fn fetch_authors(
+    config: &Config,
+    store: &dyn NotificationStore,
+    notifications: &mut [Notification],
+    subject_urls: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let missing = cached_authors(store, notifications, subject_urls)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let notifications_api = github_instance(config).user_activity().notifications();
+    for (index, url) in missing.into_iter() {
+        let looked_up = notifications_api.subject_author(&url).await;
+        record_author(store, &mut notifications[index], &url, looked_up)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the rules on changed threads, dropped threads are left out of the batch to cache and the verdicts of the
+/// others kept for `record_verdicts`.
+fn judge(
+    rules: &Rules,
+    notifications: Vec<Notification>,
+    verdicts: &mut HashMap<String, Verdict>,
+) -> Vec<Notification> {
+    notifications
         .into_iter()
-        .map(|notification| notification.into())
-        .collect();
+        .filter(|notification| match rules.evaluate(notification) {
+            Some(verdict) => {
+                let keep = !verdict.drop;
+                verdicts.insert(notification.id.clone(), verdict);
+                keep
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Carries out the verdicts once the threads are cached, read and done threads are hidden locally and queued for
+/// github. A dropped thread is removed in case an older copy is cached. Returns how many threads were dropped.
+fn record_verdicts(store: &dyn NotificationStore, verdicts: &HashMap<String, Verdict>) -> anyhow::Result<usize> {
+    for (id, verdict) in verdicts.iter() {
+        if verdict.drop {
+            store.delete_by_id(id)?;
+            continue;
+        }
+        for tag in verdict.tags.iter() {
+            store.add_tag(id, tag)?;
+        }
+        if verdict.priority {
+            store.set_pinned(id, true)?;
+        }
+        if verdict.done {
+            store.enqueue_action(id, RemoteAction::MarkAsDone)?;
+        } else if verdict.read {
+            store.enqueue_action(id, RemoteAction::MarkAsRead)?;
+        }
+        if verdict.done || verdict.read {
+            store.dismiss(id)?;
+        }
+    }
 
+    Ok(verdicts.values().filter(|verdict| verdict.drop).count())
+}
+
+/// Changed threads worth a desktop alert, those no rule silenced.
+fn alerted(notifications: &[Notification], verdicts: &HashMap<String, Verdict>) -> usize {
     notifications
+        .iter()
+        .filter(|notification| !verdicts.get(&notification.id).is_some_and(Verdict::is_silent))
+        .count()
+}
+
+fn alert_message(new_count: usize, updated_count: usize, woken_count: usize) -> Option<String> {
+    let parts: Vec<String> = [
+        (new_count, "new notifications"),
+        (updated_count, "threads with new activity"),
+        (woken_count, "snoozed notifications are back"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, label)| format!("{} {}", count, label))
+    .collect();
+
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join(", "))
+}
+
+/// Longest wait between retries of a failing poll.
+const MAX_RETRY_DELAY_SECONDS: i64 = 15 * 60;
+
+/// Doubles the wait after each failure in a row, starting from the polling interval and capped.
+fn retry_delay(interval: chrono::Duration, consecutive_failures: u32) -> chrono::Duration {
+    let doublings = consecutive_failures.saturating_sub(1).min(16);
+    (interval * 2i32.pow(doublings)).min(chrono::Duration::seconds(MAX_RETRY_DELAY_SECONDS).max(interval))
 }
 
-async fn poll_notifications() {
-    let mut cache = Cache::new();
-    cache.delete_all_before(rolling_window());
+/// Polls in a row that found nothing new and the last `X-Poll-Interval`, kept by the scheduler to adapt the interval.
+#[derive(Debug, Default)]
+struct Pace {
+    quiet_polls: u32,
+    min_interval: Option<chrono::Duration>,
+}
+
+impl Pace {
+    fn record(&mut self, changed: usize, poll_interval: Option<u32>) {
+        self.quiet_polls = if changed > 0 {
+            0
+        } else {
+            self.quiet_polls.saturating_add(1)
+        };
+        if let Some(seconds) = poll_interval {
+            self.min_interval = Some(chrono::Duration::seconds(seconds.into()));
+        }
+    }
+
+    fn interval(&self, config: &Config) -> chrono::Duration {
+        config.additional_config.get_poll_schedule().next_interval(
+            chrono::Local::now().naive_local(),
+            self.quiet_polls,
+            self.min_interval,
+        )
+    }
+}
+
+/// When the next poll is due, one interval after the last one unless the last polls failed.
+fn next_poll(health: &PollHealth, interval: chrono::Duration, polled_at: DateTime) -> DateTime {
+    match (health.consecutive_failures, health.last_error_at) {
+        (0, _) | (_, None) => polled_at + interval,
+        (failures, Some(failed_at)) => failed_at + retry_delay(interval, failures),
+    }
+}
 
-    let mut cached_notifications_map = HashMap::new();
-    for notification in cache.read_all().unwrap().iter() {
-        cached_notifications_map.insert(notification.id.clone(), true);
+/// Longest single sleep, wall clock time is checked again after it in case the machine was suspended meanwhile.
+const MAX_SLEEP_SECONDS: i64 = 5 * 60;
+
+/// Replays queued actions, wakes snoozed threads and prunes the cache before syncing, then alerts about what changed.
+/// Returns how many threads changed and the `X-Poll-Interval` github asked for.
+async fn poll_notifications(
+    store: &dyn NotificationStore,
+    config: &Config,
+    reconcile: bool,
+) -> anyhow::Result<(usize, Option<u32>)> {
+    let replayed = replay_pending_actions(config, store.read_pending_actions()?).await;
+    record_replayed_actions(store, replayed)?;
+    let woken_notifications = store.wake_snoozed(chrono::Utc::now())?;
+    let pruned = store.prune(config.additional_config.get_retention(), chrono::Utc::now())?;
+
+    let synced = sync_once(config, store, SyncMode::Incremental { reconcile }, |_, _| {}).await?;
+
+    let message = alert_message(synced.new_alerts, synced.updated_alerts, woken_notifications.len());
+    if let Some(message) = message.as_ref() {
+        if config.additional_config.get_enable_os_notifications() {
+            let notification = platform::notification::NotificationManager::new();
+            notification.send(message, Duration::from_secs(3));
+        }
+    }
+
+    info!(format!(
+        "Found {} new notifications, {} updated threads, {} read elsewhere, {} dropped by rules, {} woken from snooze, \
+        pruned {} from the cache",
+        synced.new,
+        synced.updated,
+        synced.read_elsewhere,
+        synced.dropped,
+        woken_notifications.len(),
+        pruned
+    ));
+    Ok((synced.new + synced.updated, synced.poll_interval))
+}
+
+/// Polls once and returns when the next poll is due, errors are logged and recorded for `ghostie status`. Threads read
+/// elsewhere are reconciled when `reconcile` is set, otherwise once an interval. The cache stays open between polls,
+/// it is only opened again when that failed.
+async fn scheduled_poll(config: &Config, cache: &mut Option<Cache>, pace: &mut Pace, reconcile: bool) -> DateTime {
+    if cache.is_none() {
+        match Cache::open_verified(&config.cache_file) {
+            Ok(opened) => *cache = Some(opened),
+            Err(err) => error!(format!("Failed to open the cache, {}", err)),
+        }
     }
+    let cache = match cache.as_ref() {
+        Some(cache) => cache,
+        None => return chrono::Utc::now() + pace.interval(config),
+    };
 
-    let notifications: Vec<_> = fetch_notifications()
+    if let Err(err) = cache.backup_if_due(&config.cache_file, chrono::Utc::now()) {
+        error!(format!("Failed to back up the cache, {}", err));
+    }
+    let polled = poll_notifications(cache, config, reconcile).await;
+    if let Ok((changed, poll_interval)) = polled {
+        pace.record(changed, poll_interval);
+    }
+    let interval = pace.interval(config);
+    let recorded = match polled {
+        Ok(_) => cache.record_poll_success(),
+        Err(err) => cache
+            .record_poll_failure(&err.to_string(), chrono::Utc::now())
+            .map(|failures| {
+                error!(format!(
+                    "{} ({} in a row), retrying in {}s",
+                    err,
+                    failures,
+                    retry_delay(interval, failures).num_seconds()
+                ))
+            }),
+    };
+    if let Err(err) = recorded {
+        error!(format!("Failed to record the poll, {}", err));
+    }
+
+    let health = cache.read_poll_health().unwrap_or_default();
+    next_poll(&health, interval, chrono::Utc::now())
+}
+
+/// Sleeps until `due`, or until the process is woken up early.
+async fn sleep_until(due: DateTime, wakeups: &mut Option<Receiver<Wake>>) -> Option<Wake> {
+    loop {
+        let remaining = due - chrono::Utc::now();
+        if remaining <= chrono::Duration::zero() {
+            return None;
+        }
+        let nap = remaining
+            .min(chrono::Duration::seconds(MAX_SLEEP_SECONDS))
+            .to_std()
+            .unwrap_or_default();
+
+        match wakeups {
+            Some(receiver) => match async_std::future::timeout(nap, receiver.recv()).await {
+                Ok(Ok(wake)) => return Some(wake),
+                Ok(Err(_)) => *wakeups = None,
+                Err(_) => {}
+            },
+            None => async_std::task::sleep(nap).await,
+        }
+    }
+}
+
+/// What one sync changed in the cache.
+#[derive(Debug, Default)]
+pub struct Synced {
+    pub new: usize,
+    pub updated: usize,
+    pub read_elsewhere: usize,
+    pub dropped: usize,
+    /// New and updated threads no rule silenced, worth a desktop alert
+    new_alerts: usize,
+    updated_alerts: usize,
+    /// The fewest seconds github wants between polls
+    poll_interval: Option<u32>,
+}
+
+/// Fetches notifications, runs the rules on changed threads and caches them, hides threads read elsewhere and moves
+/// the sync cursor. Shared by the background process and `ghostie sync`, which reports progress after each page.
+pub async fn sync_once<F: FnMut(u32, usize)>(
+    config: &Config,
+    store: &dyn NotificationStore,
+    mode: SyncMode,
+    progress: F,
+) -> anyhow::Result<Synced> {
+    let started = chrono::Utc::now();
+    let unread = unread_here(store.read_all()?);
+    let (cursor, since, reconcile) = match mode {
+        SyncMode::Incremental { reconcile } => {
+            let cursor = poll_since(
+                store.read_sync_cursor()?.as_ref(),
+                read_recovered_at(store)?,
+                rolling_window(config),
+            );
+            let reconcile = reconcile || reconcile_due(read_reconciled_at(store)?, started);
+            let since = if reconcile {
+                reconcile_since(cursor, &unread)
+            } else {
+                cursor
+            };
+            (cursor, since, reconcile)
+        }
+        SyncMode::Backfill { since } => (None, since, since.is_none()),
+    };
+    let mut fetched = fetch_notifications(config, since, progress)
         .await
-        .into_iter()
-        .filter(|notification| !cached_notifications_map.contains_key(&notification.id))
-        .collect();
+        .map_err(|error| anyhow::anyhow!("Failed to fetch notifications, {}", error))?;
+    let read_elsewhere = read_elsewhere(&unread, &fetched.notifications, since);
+    let (mut new_notifications, mut updated_notifications) =
+        changed_notifications(std::mem::take(&mut fetched.notifications), store, cursor)?;
 
-    cache.write_batch(&notifications).unwrap_or_else(|error| {
-        error!("\n");
-        panic!("Failed to write to the cache, {}", error)
-    });
+    let rules = config.additional_config.get_rules();
+    if rules.needs_author() {
+        fetch_authors(config, store, &mut new_notifications, &fetched.subject_urls).await?;
+        fetch_authors(config, store, &mut updated_notifications, &fetched.subject_urls).await?;
+    }
+    let mut verdicts = HashMap::new();
+    let new_notifications = judge(rules, new_notifications, &mut verdicts);
+    let updated_notifications = judge(rules, updated_notifications, &mut verdicts);
 
-    let count = notifications.len();
-    if count > 0 && Config::read().additional_config.get_enable_os_notifications() {
-        let notification = platform::notification::NotificationManager::new();
-        notification.send(format!("{} new notifications", count), Duration::from_secs(3));
+    store
+        .write_batch(&[new_notifications.as_slice(), updated_notifications.as_slice()].concat())
+        .map_err(|error| anyhow::anyhow!("Failed to write to the cache, {}", error))?;
+    let dropped = record_verdicts(store, &verdicts)?;
+    let read_elsewhere = store.mark_read_remotely(&read_elsewhere, started)?;
+    store.write_sync_cursor(&SyncCursor {
+        last_sync: started,
+        server_date: fetched.server_date,
+    })?;
+    if reconcile {
+        store.write_state(RECONCILED_AT, &timestamp(started))?;
     }
 
-    info!(format!("Found {} new notifications", count));
+    Ok(Synced {
+        new: new_notifications.len(),
+        updated: updated_notifications.len(),
+        read_elsewhere,
+        dropped,
+        new_alerts: alerted(&new_notifications, &verdicts),
+        updated_alerts: alerted(&updated_notifications, &verdicts),
+        poll_interval: fetched.poll_interval,
+    })
 }
 
-pub async fn start() {
-    let mut scheduler = AsyncScheduler::new();
-    scheduler
-        .every(
-            Config::read()
-                .additional_config
-                .get_polling_interval_seconds()
-                .seconds(),
-        )
-        .run(|| async {
-            poll_notifications().await;
-        });
+/// Polls right away, then sleeps until the next poll is due unless woken up earlier by `ghostie refresh`, or by
+/// `ghostie reload` which re-reads the configuration file first.
+pub async fn start(mut config: Config) {
+    let mut wakeups = signals::listen()
+        .map_err(|err| error!(format!("Failed to listen for refresh requests, {}", err)))
+        .ok();
 
+    let mut cache = None;
+    let mut pace = Pace::default();
+    let mut due = scheduled_poll(&config, &mut cache, &mut pace, false).await;
     loop {
-        scheduler.run_pending().await;
-        async_std::task::sleep(Duration::from_millis(100)).await;
+        match sleep_until(due, &mut wakeups).await {
+            None => due = scheduled_poll(&config, &mut cache, &mut pace, false).await,
+            Some(Wake::Refresh) => {
+                info!("Polling now, a refresh was requested");
+                pace.quiet_polls = 0;
+                due = scheduled_poll(&config, &mut cache, &mut pace, true).await;
+            }
+            Some(Wake::Reload) => match config.reload() {
+                Ok(reloaded) => {
+                    config = reloaded;
+                    due = due.min(chrono::Utc::now() + pace.interval(&config));
+                    info!(format!(
+                        "Reloaded the configuration from {}, next poll at {}",
+                        config.config_file.display(),
+                        due.with_timezone(&chrono::Local).to_rfc2822()
+                    ));
+                }
+                Err(err) => error!(format!("Keeping the current configuration, {}", err)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{Duration, Utc};
+
+    use super::{
+        alert_message, alerted, cached_authors, changed_notifications, judge, next_poll, poll_since, read_elsewhere,
+        reconcile_due, reconcile_since, record_author, record_replayed_actions, record_verdicts, retry_delay,
+        sync_since, unread_here, MAX_RETRY_DELAY_SECONDS, RECONCILE_INTERVAL_MINUTES, SYNC_OVERLAP_SECONDS,
+    };
+    use crate::cache::{
+        actions::RemoteAction,
+        notifications::{Notification, NotificationBuilder},
+        store::NotificationStore,
+        sync::{PollHealth, SyncCursor},
+        MemoryStore,
+    };
+    use crate::rules::Rules;
+
+    fn a_notification(id: &str, updated_at: &str) -> Notification {
+        NotificationBuilder::new(id).updated_at(updated_at).build()
+    }
+
+    #[test]
+    fn splits_new_and_updated_threads() {
+        let store = MemoryStore::default();
+        let mut authored = a_notification("2", "2022-12-12T18:52:24Z");
+        authored.author = Some(String::from("octocat"));
+        store
+            .write_batch(&[a_notification("1", "2022-12-12T18:52:24Z"), authored])
+            .unwrap();
+
+        let fetched = vec![
+            a_notification("1", "2022-12-12T18:52:24Z"),
+            a_notification("2", "2022-12-13T08:00:00Z"),
+            a_notification("3", "2022-12-13T08:00:00Z"),
+            a_notification("4", "2022-12-12T10:00:00Z"),
+        ];
+        let cursor = "2022-12-12T12:00:00Z".parse().ok();
+        let (new, updated) = changed_notifications(fetched, &store, cursor).unwrap();
+
+        assert_eq!(new.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["3"]);
+        assert_eq!(updated.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["2"]);
+        assert_eq!(updated[0].author.as_deref(), Some("octocat"));
+        assert_eq!(
+            alert_message(new.len(), updated.len(), 0).as_deref(),
+            Some("1 new notifications, 1 threads with new activity")
+        );
+    }
+
+    #[test]
+    fn applies_rule_verdicts() {
+        let rules = Rules::new(vec![
+            "title:^bump -> read tag:deps".parse().unwrap(),
+            "repo:octocat/spam -> drop".parse().unwrap(),
+            "reason:review_requested -> priority".parse().unwrap(),
+        ]);
+        let store = MemoryStore::default();
+        let mut spam = a_notification("3", "2022-12-12T18:52:24Z");
+        spam.name = String::from("octocat/spam");
+        store.write_batch(&[spam.clone()]).unwrap();
+
+        let mut bump = a_notification("1", "2022-12-13T08:00:00Z");
+        bump.subject = String::from("Bump serde");
+        bump.reason = String::from("subscribed");
+        let mut review = a_notification("2", "2022-12-13T08:00:00Z");
+        review.reason = String::from("review_requested");
+        spam.updated_at = String::from("2022-12-13T08:00:00Z");
+
+        let mut verdicts = HashMap::new();
+        let kept = judge(&rules, vec![bump, review, spam], &mut verdicts);
+        assert_eq!(kept.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["1", "2"]);
+        assert_eq!(alerted(&kept, &verdicts), 1);
+
+        store.write_batch(&kept).unwrap();
+        assert_eq!(record_verdicts(&store, &verdicts).unwrap(), 1);
+        assert!(store.read_by_id("3").unwrap().is_none());
+        assert!(store.read_by_id("1").unwrap().unwrap().dismissed_at.is_some());
+        assert_eq!(store.read_annotation("1").unwrap().tags, vec![String::from("deps")]);
+        assert!(store.read_by_id("2").unwrap().unwrap().pinned);
+
+        let pending = store.read_pending_actions().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            (pending[0].thread_id.as_str(), pending[0].action),
+            ("1", RemoteAction::MarkAsRead)
+        );
+    }
+
+    #[test]
+    fn remembers_author_lookups_per_subject() {
+        let store = MemoryStore::default();
+        let url = String::from("https://api.github.com/repos/octocat/Hello-World/pulls/1");
+        let subject_urls = HashMap::from([(String::from("1"), url.clone()), (String::from("2"), url.clone())]);
+
+        let mut fetched = vec![a_notification("1", "2022-12-12T18:52:24Z")];
+        assert_eq!(
+            cached_authors(&store, &mut fetched, &subject_urls).unwrap(),
+            vec![(0, url.clone())]
+        );
+        record_author(&store, &mut fetched[0], &url, Err("rate limited")).unwrap();
+        store.write_batch(&fetched).unwrap();
+        assert_eq!(store.read_by_id("1").unwrap().unwrap().author, None);
+        assert!(cached_authors(&store, &mut fetched, &subject_urls).unwrap().is_empty());
+
+        let mut updated = vec![a_notification("1", "2022-12-13T08:00:00Z")];
+        assert_eq!(
+            cached_authors(&store, &mut updated, &subject_urls).unwrap(),
+            vec![(0, url.clone())]
+        );
+        record_author(
+            &store,
+            &mut updated[0],
+            &url,
+            Ok::<_, String>(Some(String::from("octocat"))),
+        )
+        .unwrap();
+        assert_eq!(updated[0].author.as_deref(), Some("octocat"));
+
+        let mut same_subject = vec![a_notification("2", "2022-12-12T18:52:24Z")];
+        assert!(cached_authors(&store, &mut same_subject, &subject_urls)
+            .unwrap()
+            .is_empty());
+        assert_eq!(same_subject[0].author.as_deref(), Some("octocat"));
+    }
+
+    #[test]
+    fn failed_actions_stay_queued() {
+        let store = MemoryStore::default();
+        store.enqueue_action("1", RemoteAction::MarkAsRead).unwrap();
+        store.enqueue_action("2", RemoteAction::MarkAsRead).unwrap();
+        let pending = store.read_pending_actions().unwrap();
+
+        record_replayed_actions(
+            &store,
+            vec![
+                (pending[0].clone(), Ok(())),
+                (pending[1].clone(), Err(anyhow::anyhow!("offline"))),
+            ],
+        )
+        .unwrap();
+
+        let pending = store.read_pending_actions().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].thread_id, "2");
+        assert_eq!(pending[0].last_error.as_deref(), Some("offline"));
+    }
+
+    #[test]
+    fn fetches_from_the_cursor_with_an_overlap() {
+        let now = Utc::now();
+        let window = now - Duration::days(2);
+        assert_eq!(sync_since(None, window), window);
+
+        let cursor = SyncCursor {
+            last_sync: now - Duration::minutes(5),
+            server_date: Some(now - Duration::minutes(4)),
+        };
+        assert_eq!(
+            sync_since(Some(&cursor), window),
+            now - Duration::minutes(4) - Duration::seconds(SYNC_OVERLAP_SECONDS)
+        );
+
+        let stale = SyncCursor {
+            last_sync: now - Duration::days(7),
+            server_date: None,
+        };
+        assert_eq!(sync_since(Some(&stale), window), window);
+    }
+
+    #[test]
+    fn backs_off_after_failed_polls() {
+        let interval = Duration::seconds(60);
+        assert_eq!(retry_delay(interval, 1), interval);
+        assert_eq!(retry_delay(interval, 3), Duration::seconds(240));
+        assert_eq!(retry_delay(interval, 40), Duration::seconds(MAX_RETRY_DELAY_SECONDS));
+        assert_eq!(retry_delay(Duration::hours(1), 3), Duration::hours(1));
+
+        let failed_at = Utc::now();
+        let mut health = PollHealth {
+            consecutive_failures: 2,
+            last_error: Some(String::from("offline")),
+            last_error_at: Some(failed_at),
+        };
+        let polled_at = failed_at + Duration::seconds(5);
+        assert_eq!(
+            next_poll(&health, interval, polled_at),
+            failed_at + Duration::seconds(120)
+        );
+        health.consecutive_failures = 0;
+        assert_eq!(next_poll(&health, interval, polled_at), polled_at + interval);
+    }
+
+    #[test]
+    fn reconciles_threads_read_elsewhere() {
+        let mut dismissed = a_notification("3", "2022-12-01T09:00:00Z");
+        dismissed.dismissed_at = Some(String::from("2022-12-02T09:00:00Z"));
+        let unread = unread_here(vec![
+            a_notification("1", "2022-12-10T09:00:00Z"),
+            a_notification("2", "2022-12-12T18:52:24Z"),
+            dismissed,
+        ]);
+        assert_eq!(unread.len(), 2);
+
+        let cursor = "2022-12-12T18:00:00Z".parse().unwrap();
+        let since = reconcile_since(Some(cursor), &unread);
+        assert_eq!(
+            since,
+            Some(
+                "2022-12-10T09:00:00Z".parse::<chrono::DateTime<Utc>>().unwrap()
+                    - Duration::seconds(SYNC_OVERLAP_SECONDS)
+            )
+        );
+        assert_eq!(reconcile_since(None, &unread), None);
+        assert_eq!(reconcile_since(Some(cursor), &[]), Some(cursor));
+
+        let fetched = [a_notification("2", "2022-12-12T19:00:00Z")];
+        assert_eq!(read_elsewhere(&unread, &fetched, since), vec![String::from("1")]);
+        assert!(read_elsewhere(&unread, &fetched, Some(cursor)).is_empty());
+
+        let now = Utc::now();
+        assert!(reconcile_due(None, now));
+        assert!(!reconcile_due(Some(now - Duration::minutes(5)), now));
+        assert!(reconcile_due(
+            Some(now - Duration::minutes(RECONCILE_INTERVAL_MINUTES)),
+            now
+        ));
+    }
+
+    #[test]
+    fn resyncs_everything_after_a_recovery() {
+        let now = Utc::now();
+        let window = now - Duration::days(2);
+        let cursor = SyncCursor {
+            last_sync: now - Duration::minutes(5),
+            server_date: None,
+        };
+
+        assert_eq!(poll_since(None, Some(now - Duration::hours(1)), window), None);
+        assert_eq!(
+            poll_since(Some(&cursor), Some(now - Duration::minutes(1)), window),
+            None
+        );
+        assert_eq!(
+            poll_since(Some(&cursor), Some(now - Duration::minutes(10)), window),
+            Some(sync_since(Some(&cursor), window))
+        );
+        assert_eq!(poll_since(None, None, window), Some(window));
     }
 }
@@ -0,0 +1,388 @@
+use std::{fmt, str::FromStr};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::cache::notifications::Notification;
+
+/// Matches `text` against a pattern where `*` stands for any run of characters and `?` for one, ignoring case.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    t = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// One condition of a rule, written `key:value`.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// `repo:owner/name`, a glob on the full repository name
+    Repo(String),
+    /// `org:owner`, a glob on the repository owner
+    Org(String),
+    /// `reason:review_requested`, why github sent the notification
+    Reason(String),
+    /// `type:PullRequest`, the kind of subject
+    Kind(String),
+    /// `title:^chore`, a case insensitive regex on the subject title
+    Title(Regex),
+    /// `author:dependabot*`, a glob on who opened the subject
+    Author(String),
+}
+
+impl Matcher {
+    fn matches(&self, notification: &Notification) -> bool {
+        match self {
+            Matcher::Repo(pattern) => glob_match(pattern, &notification.name),
+            Matcher::Org(pattern) => glob_match(pattern, &notification.repo_owner),
+            Matcher::Reason(reason) => reason.eq_ignore_ascii_case(&notification.reason),
+            Matcher::Kind(kind) => kind.eq_ignore_ascii_case(&notification.kind),
+            Matcher::Title(regex) => regex.is_match(&notification.subject),
+            Matcher::Author(pattern) => notification
+                .author
+                .as_ref()
+                .is_some_and(|author| glob_match(pattern, author)),
+        }
+    }
+}
+
+impl FromStr for Matcher {
+    type Err = anyhow::Error;
+
+    fn from_str(matcher: &str) -> Result<Self, Self::Err> {
+        let (key, value) = matcher
+            .split_once(':')
+            .filter(|(_, value)| !value.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("expected `key:value` instead of `{}`", matcher))?;
+        match key {
+            "repo" => Ok(Matcher::Repo(value.to_owned())),
+            "org" => Ok(Matcher::Org(value.to_owned())),
+            "reason" => Ok(Matcher::Reason(value.to_owned())),
+            "type" => Ok(Matcher::Kind(value.to_owned())),
+            "title" => Ok(Matcher::Title(
+                RegexBuilder::new(value)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| anyhow::anyhow!("invalid title regex `{}`, {}", value, err))?,
+            )),
+            "author" => Ok(Matcher::Author(value.to_owned())),
+            other => anyhow::bail!(
+                "unknown matcher `{}`, expected one of repo, org, reason, type, title or author",
+                other
+            ),
+        }
+    }
+}
+
+/// What happens to a notification a rule matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Not cached at all
+    Drop,
+    /// Cached without a desktop alert
+    Mute,
+    /// Marked as read on github and hidden locally
+    Read,
+    /// Marked as done on github and hidden locally
+    Done,
+    /// Tagged locally
+    Tag(String),
+    /// Pinned to the top of the list
+    Priority,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action.split_once(':') {
+            Some(("tag", tag)) if !tag.trim_start_matches('#').is_empty() => {
+                Ok(Action::Tag(tag.trim_start_matches('#').to_owned()))
+            }
+            None if action == "drop" => Ok(Action::Drop),
+            None if action == "mute" => Ok(Action::Mute),
+            None if action == "read" => Ok(Action::Read),
+            None if action == "done" => Ok(Action::Done),
+            None if action == "priority" => Ok(Action::Priority),
+            _ => anyhow::bail!(
+                "unknown action `{}`, expected one of drop, mute, read, done, tag:<name> or priority",
+                action
+            ),
+        }
+    }
+}
+
+/// A `rule=` line of the configuration file, e.g. `author:dependabot* reason:subscribed -> read tag:deps`.
+/// Every matcher has to match for the actions to apply.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    source: String,
+    matchers: Vec<Matcher>,
+    actions: Vec<Action>,
+}
+
+impl Rule {
+    pub fn matches(&self, notification: &Notification) -> bool {
+        self.matchers.iter().all(|matcher| matcher.matches(notification))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let (matchers, actions) = split(rule)?
+            .ok_or_else(|| anyhow::anyhow!("expected `matchers -> actions`, e.g. `repo:octocat/* -> mute`"))?;
+        let matchers = matchers
+            .iter()
+            .map(|matcher| matcher.parse())
+            .collect::<anyhow::Result<Vec<Matcher>>>()?;
+        let actions = actions
+            .iter()
+            .map(|action| action.parse())
+            .collect::<anyhow::Result<Vec<Action>>>()?;
+        if matchers.is_empty() {
+            anyhow::bail!("it would match every notification, add e.g. `repo:octocat/*`");
+        }
+        if actions.is_empty() {
+            anyhow::bail!("it does nothing, add e.g. `-> mute`");
+        }
+
+        Ok(Rule {
+            source: rule.trim().to_owned(),
+            matchers,
+            actions,
+        })
+    }
+}
+
+/// Splits a rule into its matcher and action words at the first `->`. Double quotes keep spaces and `->` in a value,
+/// e.g. `title:"^bump .* from"`, and `\"` is a quote inside them.
+fn split(rule: &str) -> anyhow::Result<Option<(Vec<String>, Vec<String>)>> {
+    let mut matchers = None;
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = rule.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => quoted = !quoted,
+            '\\' if quoted && chars.peek() == Some(&'"') => word.extend(chars.next()),
+            '-' if !quoted && chars.peek() == Some(&'>') && matchers.is_none() => {
+                chars.next();
+                words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+                matchers = Some(std::mem::take(&mut words));
+            }
+            char if char.is_whitespace() && !quoted => {
+                words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            }
+            char => word.push(char),
+        }
+    }
+    if quoted {
+        anyhow::bail!("a quote is never closed");
+    }
+    words.extend((!word.is_empty()).then_some(word));
+
+    Ok(matchers.map(|matchers| (matchers, words)))
+}
+
+/// Everything the rules matching a notification ask for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verdict {
+    pub drop: bool,
+    pub mute: bool,
+    pub read: bool,
+    pub done: bool,
+    pub priority: bool,
+    pub tags: Vec<String>,
+}
+
+impl Verdict {
+    /// No desktop alert for the notification, it is dropped, muted or read straight away.
+    pub fn is_silent(&self) -> bool {
+        self.drop || self.mute || self.read || self.done
+    }
+
+    fn apply(&mut self, action: &Action) {
+        match action {
+            Action::Drop => self.drop = true,
+            Action::Mute => self.mute = true,
+            Action::Read => self.read = true,
+            Action::Done => self.done = true,
+            Action::Priority => self.priority = true,
+            Action::Tag(tag) if !self.tags.contains(tag) => self.tags.push(tag.clone()),
+            Action::Tag(_) => {}
+        }
+    }
+}
+
+/// The rules of the configuration file in the order they are written, every matching rule applies.
+#[derive(Debug, Clone, Default)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Rules(rules)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether a rule matches on the author, which github only reports by fetching the subject.
+    pub fn needs_author(&self) -> bool {
+        self.iter()
+            .flat_map(|rule| rule.matchers.iter())
+            .any(|matcher| matches!(matcher, Matcher::Author(_)))
+    }
+
+    /// Combines the actions of every rule matching `notification`, `None` when no rule matches.
+    pub fn evaluate(&self, notification: &Notification) -> Option<Verdict> {
+        let mut matching = self.iter().filter(|rule| rule.matches(notification)).peekable();
+        matching.peek()?;
+
+        let mut verdict = Verdict::default();
+        for action in matching.flat_map(|rule| rule.actions.iter()) {
+            verdict.apply(action);
+        }
+        Some(verdict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, Rule, Rules, Verdict};
+    use crate::cache::notifications::{Notification, NotificationBuilder};
+
+    fn a_notification(name: &str, reason: &str, subject: &str, author: Option<&str>) -> Notification {
+        let builder = NotificationBuilder::new("1").name(name).reason(reason).subject(subject);
+        match author {
+            Some(author) => builder.author(author).build(),
+            None => builder.build(),
+        }
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match("octocat/*", "octocat/Hello-World"));
+        assert!(glob_match("*[bot]", "dependabot[bot]"));
+        assert!(glob_match("Hello-?orld", "hello-world"));
+        assert!(glob_match("*o*o*", "octocat/foo"));
+        assert!(!glob_match("octocat/*", "github/docs"));
+        assert!(!glob_match("octo", "octocat"));
+    }
+
+    #[test]
+    fn combines_matching_rules() {
+        let rules = Rules::new(vec![
+            "author:dependabot* reason:subscribed -> read tag:deps".parse().unwrap(),
+            "org:octocat title:^bump -> mute tag:#deps".parse().unwrap(),
+            "reason:review_requested type:pullrequest -> priority".parse().unwrap(),
+        ]);
+        assert!(rules.needs_author());
+
+        let bump = a_notification(
+            "octocat/Hello-World",
+            "subscribed",
+            "Bump serde",
+            Some("dependabot[bot]"),
+        );
+        assert_eq!(
+            rules.evaluate(&bump),
+            Some(Verdict {
+                read: true,
+                mute: true,
+                tags: vec![String::from("deps")],
+                ..Verdict::default()
+            })
+        );
+
+        let review = a_notification("github/docs", "review_requested", "Fix typo", None);
+        assert_eq!(
+            rules.evaluate(&review),
+            Some(Verdict {
+                priority: true,
+                ..Verdict::default()
+            })
+        );
+        assert_eq!(rules.evaluate(&review).map(|verdict| verdict.is_silent()), Some(false));
+
+        let unknown_author = a_notification("github/docs", "subscribed", "Bump serde", None);
+        assert_eq!(rules.evaluate(&unknown_author), None);
+    }
+
+    #[test]
+    fn quotes_values_with_spaces() {
+        let rules = Rules::new(vec![
+            r#"title:"^bump .* from" -> read"#.parse().unwrap(),
+            r#"title:"a -> b \"c\"" -> tag:arrows"#.parse().unwrap(),
+        ]);
+
+        let bump = a_notification("octocat/Hello-World", "subscribed", "Bump serde from 1.0 to 1.1", None);
+        assert_eq!(
+            rules.evaluate(&bump),
+            Some(Verdict {
+                read: true,
+                ..Verdict::default()
+            })
+        );
+        let arrows = a_notification("octocat/Hello-World", "subscribed", r#"Rename a -> b "c""#, None);
+        assert_eq!(
+            rules.evaluate(&arrows),
+            Some(Verdict {
+                tags: vec![String::from("arrows")],
+                ..Verdict::default()
+            })
+        );
+        let unrelated = a_notification("octocat/Hello-World", "subscribed", "Bump serde", None);
+        assert_eq!(rules.evaluate(&unrelated), None);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in [
+            "repo:octocat/*",
+            "-> drop",
+            "repo:octocat/* ->",
+            "label:bug -> drop",
+            "title:( -> mute",
+            "repo: -> mute",
+            "reason:mention -> archive",
+            "reason:mention -> tag:",
+            r#"title:"^bump -> mute"#,
+        ] {
+            assert!(rule.parse::<Rule>().is_err(), "{}", rule);
+        }
+    }
+}
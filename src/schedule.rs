@@ -0,0 +1,183 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::snooze::{parse_time, parse_weekday};
+
+/// Hours of the day polling runs at the work interval, e.g. `09:00-18:00`. A range past midnight is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl WorkHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for WorkHours {
+    type Err = anyhow::Error;
+
+    fn from_str(hours: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("Could not understand work hours `{}`, expected e.g. 09:00-18:00", hours);
+        let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+        Ok(WorkHours {
+            start: parse_time(&start.trim().to_lowercase()).ok_or_else(invalid)?,
+            end: parse_time(&end.trim().to_lowercase()).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Days of the week work hours apply on, e.g. `mon-fri` or `mon,wed,fri`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkDays([bool; 7]);
+
+impl WorkDays {
+    fn contains(&self, day: Weekday) -> bool {
+        self.0[day.num_days_from_monday() as usize]
+    }
+}
+
+impl Default for WorkDays {
+    fn default() -> Self {
+        WorkDays([true, true, true, true, true, false, false])
+    }
+}
+
+impl FromStr for WorkDays {
+    type Err = anyhow::Error;
+
+    fn from_str(days: &str) -> Result<Self, Self::Err> {
+        let weekday = |day: &str| {
+            parse_weekday(&day.trim().to_lowercase())
+                .ok_or_else(|| anyhow::anyhow!("Unknown day `{}`, expected e.g. mon-fri or mon,wed,fri", day.trim()))
+        };
+
+        let mut work_days = [false; 7];
+        for part in days.split(',').filter(|part| !part.trim().is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (weekday(first)?, weekday(last)?),
+                None => (weekday(part)?, weekday(part)?),
+            };
+            let mut day = first;
+            loop {
+                work_days[day.num_days_from_monday() as usize] = true;
+                if day == last {
+                    break;
+                }
+                day = day.succ();
+            }
+        }
+        Ok(WorkDays(work_days))
+    }
+}
+
+/// How often the background process polls: `interval` while there is activity, backing off by doubling up to
+/// `max_interval` while nothing happens, and `off_hours_interval` outside work hours when they are set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollSchedule {
+    pub interval: Duration,
+    pub max_interval: Duration,
+    pub off_hours_interval: Duration,
+    pub work_hours: Option<WorkHours>,
+    pub work_days: WorkDays,
+}
+
+impl PollSchedule {
+    fn is_off_hours(&self, now: NaiveDateTime) -> bool {
+        match self.work_hours {
+            Some(hours) => !(self.work_days.contains(now.weekday()) && hours.contains(now.time())),
+            None => false,
+        }
+    }
+
+    /// How long to wait before the next poll at local time `now`, after `quiet_polls` polls in a row found nothing
+    /// new. Never shorter than `min_interval`, the `X-Poll-Interval` github asked for.
+    pub fn next_interval(&self, now: NaiveDateTime, quiet_polls: u32, min_interval: Option<Duration>) -> Duration {
+        let interval = if self.is_off_hours(now) {
+            self.off_hours_interval
+        } else {
+            let backed_off = self.interval * 2i32.pow(quiet_polls.min(16));
+            backed_off.min(self.max_interval.max(self.interval))
+        };
+
+        interval.max(min_interval.unwrap_or_else(Duration::zero))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+    use super::{PollSchedule, WorkDays, WorkHours};
+
+    /// 2022-12-12 is a Monday.
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 12, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn a_schedule() -> PollSchedule {
+        PollSchedule {
+            interval: Duration::seconds(30),
+            max_interval: Duration::minutes(5),
+            off_hours_interval: Duration::minutes(15),
+            work_hours: Some("09:00-18:00".parse().unwrap()),
+            work_days: "mon-fri".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn backs_off_while_quiet() {
+        let schedule = a_schedule();
+        assert_eq!(schedule.next_interval(at(12, 10), 0, None), Duration::seconds(30));
+        assert_eq!(schedule.next_interval(at(12, 10), 2, None), Duration::seconds(120));
+        assert_eq!(schedule.next_interval(at(12, 10), 40, None), Duration::minutes(5));
+    }
+
+    #[test]
+    fn follows_work_hours() {
+        let schedule = a_schedule();
+        assert_eq!(schedule.next_interval(at(12, 20), 0, None), Duration::minutes(15));
+        assert_eq!(schedule.next_interval(at(17, 10), 0, None), Duration::minutes(15));
+
+        let always = PollSchedule {
+            work_hours: None,
+            ..schedule
+        };
+        assert_eq!(always.next_interval(at(17, 10), 0, None), Duration::seconds(30));
+    }
+
+    #[test]
+    fn never_polls_faster_than_github_allows() {
+        let schedule = a_schedule();
+        assert_eq!(
+            schedule.next_interval(at(12, 10), 0, Some(Duration::seconds(60))),
+            Duration::seconds(60)
+        );
+        assert_eq!(
+            schedule.next_interval(at(12, 20), 0, Some(Duration::seconds(60))),
+            Duration::minutes(15)
+        );
+    }
+
+    #[test]
+    fn parses_hours_and_days() {
+        let night: WorkHours = "22:00-6am".parse().unwrap();
+        assert!(night.contains(at(12, 23).time()) && night.contains(at(12, 5).time()));
+        assert!(!night.contains(at(12, 12).time()));
+        assert!("9 to 5".parse::<WorkHours>().is_err());
+
+        let days: WorkDays = "fri-mon, wed".parse().unwrap();
+        assert_eq!(days, WorkDays([true, false, true, false, true, true, true]));
+        assert!("weekdays".parse::<WorkDays>().is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+/// Hour of the day used when only a day is given, e.g. "tomorrow" or "monday".
+const DEFAULT_HOUR: u32 = 9;
+
+/// Parses expressions like "in 2 hours", "30m", "tomorrow 9am", "friday 14:30" or an RFC 3339 timestamp
+/// into the moment a snoozed notification should come back.
+pub fn parse_until(input: &str) -> Result<chrono::DateTime<Utc>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    let until = parse_local(input, Local::now().naive_local())?;
+    match Local.from_local_datetime(&until).earliest() {
+        Some(datetime) => Ok(datetime.with_timezone(&Utc)),
+        None => anyhow::bail!("`{}` does not exist in the local timezone", input),
+    }
+}
+
+/// Parses a date like "2022-11-01", taken as local midnight, or an RFC 3339 timestamp, e.g. to bound a backfill.
+pub fn parse_date(input: &str) -> Result<chrono::DateTime<Utc>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Could not understand `{}`, expected a date like 2022-11-01", input))?;
+    match Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+    {
+        Some(datetime) => Ok(datetime.with_timezone(&Utc)),
+        None => anyhow::bail!("`{}` does not exist in the local timezone", input),
+    }
+}
+
+fn parse_local(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let input = input.trim().to_lowercase();
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    let until = match words.as_slice() {
+        ["in", amount @ ..] | amount => match parse_duration(&amount.join(" ")) {
+            Some(duration) => Some(duration.and_then(|duration| {
+                now.checked_add_signed(duration)
+                    .ok_or_else(|| anyhow::anyhow!("`{}` is too far in the future", input))
+            })?),
+            None => parse_day_and_time(&words, now),
+        },
+    };
+
+    match until {
+        Some(until) if until > now => Ok(until),
+        Some(_) => anyhow::bail!("`{}` is in the past", input),
+        None => anyhow::bail!(
+            "Could not understand `{}`, try something like \"in 2 hours\", \"tomorrow 9am\" or \"friday\"",
+            input
+        ),
+    }
+}
+
+/// "2 hours", "2h", "1 day", "45 mins", `None` when the input isn't a duration at all and an error when the amount
+/// doesn't fit in one.
+fn parse_duration(input: &str) -> Option<Result<Duration>> {
+    let input = input.replace(' ', "");
+    let split_at = input.find(|c: char| !c.is_ascii_digit()).filter(|index| *index > 0)?;
+    let (amount, unit) = input.split_at(split_at);
+    let unit_seconds: u64 = match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(
+        amount
+            .parse::<u64>()
+            .ok()
+            .and_then(|amount| amount.checked_mul(unit_seconds))
+            .and_then(|seconds| Duration::from_std(std::time::Duration::from_secs(seconds)).ok())
+            .ok_or_else(|| anyhow::anyhow!("{} {} is too long", amount, unit)),
+    )
+}
+
+/// "9am", "9:30pm", "14:30".
+pub(crate) fn parse_time(input: &str) -> Option<NaiveTime> {
+    let (clock, offset) = match input.strip_suffix("am").or_else(|| input.strip_suffix("pm")) {
+        Some(clock) => (clock, if input.ends_with("pm") { 12 } else { 0 }),
+        None => (input, 0),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+
+    let is_twelve_hour_clock = input.ends_with("am") || input.ends_with("pm");
+    if is_twelve_hour_clock && !(1..=12).contains(&hour) {
+        return None;
+    }
+    let hour = if is_twelve_hour_clock { hour % 12 + offset } else { hour };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+pub(crate) fn parse_weekday(input: &str) -> Option<Weekday> {
+    match input {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// "tomorrow", "today 5pm", "monday 9am", "next week" or a bare time which rolls over to tomorrow once passed.
+fn parse_day_and_time(words: &[&str], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let default_time = NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0)?;
+    let today = now.date();
+
+    let (day, time) = match words {
+        ["next", "week"] => {
+            let days_until_monday = 7 - today.weekday().num_days_from_monday() as i64;
+            return Some((today + Duration::days(days_until_monday)).and_time(default_time));
+        }
+        [day] => match parse_time(day) {
+            Some(time) => {
+                let until = today.and_time(time);
+                return Some(if until > now { until } else { until + Duration::days(1) });
+            }
+            None => (*day, default_time),
+        },
+        [day, time] => (*day, parse_time(time)?),
+        _ => return None,
+    };
+
+    let date = match day {
+        "today" => today,
+        "tomorrow" => today + Duration::days(1),
+        weekday => {
+            let weekday = parse_weekday(weekday)?;
+            let days_ahead =
+                (weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64).rem_euclid(7);
+            today + Duration::days(if days_ahead == 0 { 7 } else { days_ahead })
+        }
+    };
+
+    Some(date.and_time(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{parse_date, parse_local};
+
+    // A wednesday afternoon
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 12, 14)
+            .unwrap()
+            .and_hms_opt(15, 30, 0)
+            .unwrap()
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 12, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_relative_durations() {
+        assert_eq!(parse_local("in 2 hours", now()).unwrap(), at(14, 17, 30));
+        assert_eq!(parse_local("30m", now()).unwrap(), at(14, 16, 0));
+        assert_eq!(parse_local("in 1 day", now()).unwrap(), at(15, 15, 30));
+        assert_eq!(parse_local("1w", now()).unwrap(), at(21, 15, 30));
+    }
+
+    #[test]
+    fn parses_days_and_times() {
+        assert_eq!(parse_local("tomorrow 9am", now()).unwrap(), at(15, 9, 0));
+        assert_eq!(parse_local("tomorrow", now()).unwrap(), at(15, 9, 0));
+        assert_eq!(parse_local("today 5:15pm", now()).unwrap(), at(14, 17, 15));
+        assert_eq!(parse_local("friday 14:30", now()).unwrap(), at(16, 14, 30));
+        assert_eq!(parse_local("wednesday", now()).unwrap(), at(21, 9, 0));
+        assert_eq!(parse_local("next week", now()).unwrap(), at(19, 9, 0));
+        assert_eq!(parse_local("12am", now()).unwrap(), at(15, 0, 0));
+        assert_eq!(parse_local("6pm", now()).unwrap(), at(14, 18, 0));
+    }
+
+    #[test]
+    fn rejects_past_and_unknown_expressions() {
+        assert!(parse_local("today 9am", now()).is_err());
+        assert!(parse_local("someday", now()).is_err());
+        assert!(parse_local("13pm", now()).is_err());
+        assert!(parse_local("in 99999999999999 weeks", now()).is_err());
+        assert!(parse_local("in 99999999 weeks", now()).is_err());
+        assert!(parse_local("99999999999999999999999m", now()).is_err());
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(
+            parse_date("2022-12-12T18:52:24Z").unwrap().to_rfc3339(),
+            "2022-12-12T18:52:24+00:00"
+        );
+        assert_eq!(
+            parse_date("2022-11-01")
+                .unwrap()
+                .with_timezone(&chrono::Local)
+                .date_naive(),
+            NaiveDate::from_ymd_opt(2022, 11, 1).unwrap()
+        );
+        assert!(parse_date("last tuesday").is_err());
+    }
+}
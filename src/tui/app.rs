@@ -1,13 +1,75 @@
-use crate::tui::list::StatefulList;
+use std::collections::HashMap;
+
+use crate::{
+    cache::{actions::PendingAction, annotations::Annotation},
+    tui::list::StatefulList,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Inbox,
+    Snoozed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Snooze,
+    Note,
+    Tag,
+    Filter,
+    Search,
+}
+
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub value: String,
+    pub error: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Self {
+        Self::with_value(kind, String::new())
+    }
+
+    pub fn with_value(kind: PromptKind, value: String) -> Self {
+        Self {
+            kind,
+            value,
+            error: None,
+        }
+    }
+}
 
 pub struct TerminalApp<T> {
     pub items: StatefulList<T>,
+    pub pending_actions: Vec<PendingAction>,
+    pub view: View,
+    pub prompt: Option<Prompt>,
+    pub annotations: HashMap<String, Annotation>,
+    pub tag_filter: Option<String>,
+    pub search: Option<String>,
+    /// When the background process was last asked to poll, the list is reloaded for a while after
+    pub refreshed_at: Option<std::time::Instant>,
+    /// The last cache read or write that failed, shown in the status line until the next key press
+    pub error: Option<String>,
 }
 
 impl<T> TerminalApp<T> {
     pub fn create_list(items: Vec<T>) -> TerminalApp<T> {
         TerminalApp {
             items: StatefulList::with_items(items),
+            pending_actions: vec![],
+            view: View::Inbox,
+            prompt: None,
+            annotations: HashMap::new(),
+            tag_filter: None,
+            search: None,
+            refreshed_at: None,
+            error: None,
         }
     }
+
+    pub fn replace_items(&mut self, items: Vec<T>) {
+        self.items = StatefulList::with_items(items);
+    }
 }
@@ -14,6 +14,9 @@ impl<T> StatefulList<T> {
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -28,6 +31,9 @@ impl<T> StatefulList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -53,6 +59,11 @@ impl<T> StatefulList<T> {
         let i = self.state.selected();
         if let Some(index) = i {
             self.items.remove(index);
+            if self.items.is_empty() {
+                self.unselect();
+            } else if index >= self.items.len() {
+                self.state.select(Some(self.items.len() - 1));
+            }
         };
     }
 }
@@ -4,7 +4,6 @@ use std::{
 };
 
 use anyhow::Result;
-use async_std::task;
 use chrono::DateTime;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
@@ -16,21 +15,33 @@ use tui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame, Terminal,
 };
 
+use crate::actions;
 use crate::cache::{
+    actions::PendingAction,
     notifications::Notification,
-    read::{mark_as_read, read_all_notifications},
+    read::{
+        add_tag, mark_all_as_seen, read_all_notifications, read_annotations, read_pending_actions,
+        read_snoozed_notifications, remove_tag, search_notifications, set_note, set_pinned, snooze, unsnooze,
+    },
+    store::NotificationStore,
 };
-use crate::tui::app::TerminalApp as App;
+use crate::configuration::config::Config;
+use crate::daemon::{daemonize::Daemon, signals::Wake};
+use crate::snooze::parse_until;
+use crate::tui::app::{Prompt, PromptKind, TerminalApp as App, View};
 
-pub fn open() -> Result<()> {
-    terminal()
+/// How long the list keeps reloading from the cache after asking the background process to poll.
+const REFRESH_RELOAD_WINDOW: Duration = Duration::from_secs(10);
+
+pub fn open(store: &dyn NotificationStore, config: &Config) -> Result<()> {
+    terminal(store, config)
 }
 
-fn terminal() -> Result<()> {
+fn terminal(store: &dyn NotificationStore, config: &Config) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -40,11 +51,14 @@ fn terminal() -> Result<()> {
 
     let tick_rate = Duration::from_millis(250);
 
-    let mut list = read_all_notifications();
-    list.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-
-    let app = App::create_list(list);
-    let res = start_app(&mut terminal, app, tick_rate);
+    let mut app = App::create_list(vec![]);
+    let loaded = reload(store, &mut app).and_then(|_| read_pending_actions(store));
+    match loaded {
+        Ok(pending_actions) => app.pending_actions = pending_actions,
+        Err(err) => app.error = Some(err.to_string()),
+    }
+    let res = start_app(store, config, &mut terminal, app, tick_rate);
+    let seen = mark_all_as_seen(store);
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
@@ -54,10 +68,52 @@ fn terminal() -> Result<()> {
         println!("{:?}", err)
     }
 
+    seen
+}
+
+/// Shows a failed cache read or write in the status line, the TUI keeps running e.g. while another process holds
+/// the cache lock for longer than the retries wait.
+fn report(app: &mut App<Notification>, result: anyhow::Result<()>) {
+    if let Err(err) = result {
+        app.error = Some(err.to_string());
+    }
+}
+
+fn load_items(store: &dyn NotificationStore, view: View, search: Option<&str>) -> anyhow::Result<Vec<Notification>> {
+    match (view, search) {
+        (_, Some(query)) => search_notifications(store, query),
+        (View::Inbox, None) => {
+            let mut list = read_all_notifications(store)?;
+            list.sort_by(|a, b| b.pinned.cmp(&a.pinned).then(b.updated_at.cmp(&a.updated_at)));
+            Ok(list)
+        }
+        (View::Snoozed, None) => read_snoozed_notifications(store),
+    }
+}
+
+/// Reloads the current view from the cache, keeping the selected notification when it is still listed.
+fn reload(store: &dyn NotificationStore, app: &mut App<Notification>) -> anyhow::Result<()> {
+    let selected = app.items.state.selected();
+    let selected_id = app.items.current().map(|n| n.id.clone());
+
+    app.annotations = read_annotations(store)?;
+    let mut items = load_items(store, app.view, app.search.as_deref())?;
+    if let Some(tag) = &app.tag_filter {
+        items.retain(|n| app.annotations.get(&n.id).is_some_and(|a| a.tags.contains(tag)));
+    }
+    app.replace_items(items);
+
+    let total = app.items.items.len();
+    let position = selected_id
+        .and_then(|id| app.items.items.iter().position(|n| n.id == id))
+        .or_else(|| selected.filter(|_| total > 0).map(|index| index.min(total - 1)));
+    app.items.state.select(position);
     Ok(())
 }
 
 fn start_app<B: Backend>(
+    store: &dyn NotificationStore,
+    config: &Config,
     terminal: &mut Terminal<B>,
     mut app: App<Notification>,
     tick_rate: Duration,
@@ -71,47 +127,199 @@ fn start_app<B: Backend>(
             .unwrap_or_else(|| Duration::from_secs(0));
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('c') | KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
-                    KeyCode::Left => app.items.unselect(),
-                    KeyCode::Down => app.items.next(),
-                    KeyCode::Up => app.items.previous(),
-                    KeyCode::Char('m') => mark_notification_as_read(&mut app),
-                    KeyCode::Enter => open_url_in_browser(&app),
-                    _ => {}
+                if let Some(prompt) = app.prompt.as_mut() {
+                    match key.code {
+                        KeyCode::Esc => app.prompt = None,
+                        KeyCode::Enter => submit_prompt(store, &mut app),
+                        KeyCode::Backspace => {
+                            prompt.value.pop();
+                        }
+                        KeyCode::Char(c) => prompt.value.push(c),
+                        _ => {}
+                    }
+                } else {
+                    app.error = None;
+                    let result = match key.code {
+                        KeyCode::Char('c') | KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('m') if app.view == View::Inbox => mark_notification_as_read(store, &mut app),
+                        KeyCode::Char('p') if app.view == View::Inbox => toggle_pinned(store, &mut app),
+                        KeyCode::Char('u') if app.view == View::Snoozed => unsnooze_notification(store, &mut app),
+                        KeyCode::Char('z') => toggle_snoozed_view(store, &mut app),
+                        KeyCode::Char('r') => refresh(store, config, &mut app),
+                        KeyCode::Enter => open_url_in_browser(store, config, &app),
+                        code => {
+                            navigate(&mut app, code);
+                            Ok(())
+                        }
+                    };
+                    report(&mut app, result);
                 }
             }
         }
         if last_tick.elapsed() >= tick_rate {
+            if app.refreshed_at.is_some_and(|at| at.elapsed() < REFRESH_RELOAD_WINDOW) && app.prompt.is_none() {
+                let reloaded = reload(store, &mut app);
+                report(&mut app, reloaded);
+            }
             last_tick = Instant::now();
         }
     }
 }
 
-fn mark_notification_as_read(app: &mut App<Notification>) {
+/// Keys that move the selection or open a prompt, without touching the cache.
+fn navigate(app: &mut App<Notification>, code: KeyCode) {
+    match code {
+        KeyCode::Left => app.items.unselect(),
+        KeyCode::Down => app.items.next(),
+        KeyCode::Up => app.items.previous(),
+        KeyCode::Char('s') if app.view == View::Inbox => open_prompt(app, PromptKind::Snooze),
+        KeyCode::Char('n') => open_prompt(app, PromptKind::Note),
+        KeyCode::Char('t') => open_prompt(app, PromptKind::Tag),
+        KeyCode::Char('f') => open_prompt(app, PromptKind::Filter),
+        KeyCode::Char('/') => open_prompt(app, PromptKind::Search),
+        _ => {}
+    }
+}
+
+fn mark_notification_as_read(store: &dyn NotificationStore, app: &mut App<Notification>) -> anyhow::Result<()> {
     if let Some(current) = app.items.current() {
-        task::block_on(async {
-            crate::poll::mark_notification_as_read(&current.id).await;
-        });
-        mark_as_read(&current.id);
+        actions::mark_as_read(store, current)?;
         app.items.mark();
+        app.pending_actions = read_pending_actions(store)?;
     }
+    Ok(())
+}
+
+fn open_prompt(app: &mut App<Notification>, kind: PromptKind) {
+    let current = app.items.current();
+    app.prompt = match (kind, current) {
+        (PromptKind::Filter, _) => Some(Prompt::with_value(kind, app.tag_filter.clone().unwrap_or_default())),
+        (PromptKind::Search, _) => Some(Prompt::with_value(kind, app.search.clone().unwrap_or_default())),
+        (PromptKind::Note, Some(current)) => Some(Prompt::with_value(
+            kind,
+            app.annotations
+                .get(&current.id)
+                .and_then(|a| a.note.clone())
+                .unwrap_or_default(),
+        )),
+        (_, Some(_)) => Some(Prompt::new(kind)),
+        (_, None) => None,
+    };
 }
 
-fn open_url_in_browser(app: &App<Notification>) {
+fn submit_prompt(store: &dyn NotificationStore, app: &mut App<Notification>) {
+    let prompt = match app.prompt.take() {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    let value = prompt.value.trim();
+
+    let result = match (prompt.kind, app.items.current().map(|n| n.id.clone())) {
+        (PromptKind::Filter, _) => {
+            app.tag_filter = Some(value.trim_start_matches('#').to_string()).filter(|tag| !tag.is_empty());
+            Ok(())
+        }
+        (PromptKind::Search, _) => {
+            app.search = Some(value.to_string()).filter(|query| !query.is_empty());
+            app.items.unselect();
+            Ok(())
+        }
+        (PromptKind::Snooze, Some(id)) => parse_until(value).and_then(|until| snooze(store, &id, until).map(|_| ())),
+        (PromptKind::Note, Some(id)) => set_note(store, &id, value),
+        (PromptKind::Tag, Some(id)) => match value.strip_prefix('-') {
+            Some(tag) => remove_tag(store, &id, tag.trim_start_matches('#')),
+            None if !value.is_empty() => add_tag(store, &id, value.trim_start_matches('#')),
+            None => Ok(()),
+        },
+        (_, None) => Ok(()),
+    };
+
+    match result {
+        Ok(_) => {
+            let reloaded = reload(store, app);
+            report(app, reloaded)
+        }
+        Err(err) => {
+            app.prompt = Some(Prompt {
+                error: Some(err.to_string()),
+                ..prompt
+            })
+        }
+    }
+}
+
+fn unsnooze_notification(store: &dyn NotificationStore, app: &mut App<Notification>) -> anyhow::Result<()> {
     if let Some(current) = app.items.current() {
-        if open::that(current.url.clone()).is_err() {
-            println!("Could not open url: {} in a browser", current.url)
-        };
-        mark_as_read(&current.id);
+        unsnooze(store, &current.id)?;
+        app.items.mark();
     }
+    Ok(())
 }
 
-fn help_block() -> Block<'static> {
+fn toggle_pinned(store: &dyn NotificationStore, app: &mut App<Notification>) -> anyhow::Result<()> {
+    if let Some(current) = app.items.current() {
+        set_pinned(store, &current.id, !current.pinned)?;
+        reload(store, app)?;
+    }
+    Ok(())
+}
+
+/// Asks the background process to poll now and picks up its results as they land in the cache.
+fn refresh(store: &dyn NotificationStore, config: &Config, app: &mut App<Notification>) -> anyhow::Result<()> {
+    if let Ok(true) = Daemon::wake(config, Wake::Refresh) {
+        app.refreshed_at = Some(Instant::now());
+    }
+    reload(store, app)
+}
+
+fn toggle_snoozed_view(store: &dyn NotificationStore, app: &mut App<Notification>) -> anyhow::Result<()> {
+    app.view = match app.view {
+        View::Inbox => View::Snoozed,
+        View::Snoozed => View::Inbox,
+    };
+    app.items.unselect();
+    reload(store, app)
+}
+
+fn open_url_in_browser(store: &dyn NotificationStore, config: &Config, app: &App<Notification>) -> anyhow::Result<()> {
+    match app.items.current() {
+        Some(current) => actions::open(store, config, current),
+        None => Ok(()),
+    }
+}
+
+fn prompt_help(prompt: &Prompt) -> String {
+    let (label, hint) = match prompt.kind {
+        PromptKind::Snooze => ("snooze until", "e.g. in 2 hours, tomorrow 9am, friday"),
+        PromptKind::Note => ("note", "leave empty to remove"),
+        PromptKind::Tag => ("tag", "prefix with - to remove"),
+        PromptKind::Filter => ("filter by tag", "leave empty to show all"),
+        PromptKind::Search => ("search", "subject, repository, reason, notes and tags"),
+    };
+    let hint = prompt.error.as_deref().unwrap_or(hint);
+
+    format!(
+        "    {}: {}▏    {}    (enter) confirm    (esc) cancel    ",
+        label, prompt.value, hint
+    )
+}
+
+fn help_block(app: &App<Notification>) -> Block<'static> {
+    let help = match (&app.prompt, app.view) {
+        (Some(prompt), _) => prompt_help(prompt),
+        (None, _) if app.error.is_some() => format!("    {}    (any key) dismiss    ", app.error.as_deref().unwrap_or_default()),
+        (None, View::Inbox) => "    (m) mark as read    (p) pin    (s) snooze    (n) note    (t) tag    (f) filter    (/) search    (z) snoozed    (r) refresh    (↑/↓) scroll    (q/esc) quit    (enter) open in browser    ".to_string(),
+        (None, View::Snoozed) => "    (u) unsnooze    (n) note    (t) tag    (f) filter    (/) search    (z) inbox    (r) refresh    (↑/↓) scroll    (q/esc) quit    (enter) open in browser    ".to_string(),
+    };
+
+    let color = if app.prompt.is_none() && app.error.is_some() {
+        Color::Red
+    } else {
+        Color::Green
+    };
     Block::default().title(Span::styled(
-        "    (m) mark as read    (↑) scroll up    (↓) scroll down    (q/esc) quit    (enter) open in browser    "
-            .to_string(),
-        Style::default().add_modifier(Modifier::BOLD).fg(Color::Green),
+        help,
+        Style::default().add_modifier(Modifier::BOLD).fg(color),
     ))
 }
 
@@ -132,6 +340,69 @@ fn parse_into_duration(updated_at: &str) -> String {
     format!("{} days", duration.num_days())
 }
 
+fn pending_actions_status(pending_actions: &[PendingAction]) -> String {
+    if pending_actions.is_empty() {
+        return String::new();
+    }
+
+    let failed = pending_actions
+        .iter()
+        .filter(|action| action.last_error.is_some())
+        .count();
+    match pending_actions
+        .iter()
+        .rev()
+        .find_map(|action| action.last_error.as_ref())
+    {
+        Some(last_error) => format!(
+            "- {} actions pending, {} failed ({}) ",
+            pending_actions.len(),
+            failed,
+            last_error
+        ),
+        None => format!("- {} actions pending ", pending_actions.len()),
+    }
+}
+
+fn preview(app: &App<Notification>) -> Paragraph<'static> {
+    let block = Block::default().borders(Borders::ALL).title(" preview ");
+    let current = match app.items.current() {
+        Some(current) => current,
+        None => return Paragraph::new("Select a notification to preview it").block(block),
+    };
+
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            current.subject.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(format!("{} · {} · {}", current.name, current.kind, current.reason)),
+        Spans::from(format!("updated {} ago", parse_into_duration(&current.updated_at))),
+        Spans::from(current.url.clone()),
+    ];
+
+    if let Some(annotation) = app.annotations.get(&current.id) {
+        if !annotation.tags.is_empty() {
+            let tags: Vec<String> = annotation.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(Span::styled(
+                tags.join(" "),
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+        if let Some(note) = &annotation.note {
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(Span::styled(
+                "note",
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            lines.extend(note.lines().map(|line| Spans::from(line.to_string())));
+        }
+    }
+
+    Paragraph::new(lines).block(block).wrap(Wrap { trim: false })
+}
+
 fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App<Notification>) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -139,35 +410,91 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App<Notification>) {
         .vertical_margin(5)
         .horizontal_margin(10)
         .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+        .split(chunks[0]);
 
     let items = &app.items.items;
     let total_notifications = items.len();
+    let view_name = match (app.view, &app.search) {
+        (View::Snoozed, None) => "snoozed ",
+        _ => "",
+    };
+    let total_pinned = items.iter().filter(|n| n.pinned).count();
+    let pinned_status = if total_pinned > 0 {
+        format!("({} pinned) ", total_pinned)
+    } else {
+        String::new()
+    };
+    let filter_status = match (&app.search, &app.tag_filter) {
+        (Some(query), Some(tag)) => format!("matching \"{}\" tagged #{} ", query, tag),
+        (Some(query), None) => format!("matching \"{}\" ", query),
+        (None, Some(tag)) => format!("tagged #{} ", tag),
+        (None, None) => String::new(),
+    };
 
     let items: Vec<ListItem> = items
         .iter()
         .enumerate()
         .map(|(index, n)| {
-            let lines = vec![
+            let activity_marker = if n.new_activity { "● " } else { "" };
+            let pinned_marker = if n.pinned { "📌 " } else { "" };
+            let read_marker = if n.dismissed_at.is_some() { "✓ " } else { "" };
+            let mut lines = vec![
                 Spans::from(Span::styled(
-                    format!("({}) [{}] {}", index + 1, n.kind.to_lowercase(), n.subject),
+                    format!(
+                        "{}{}{}({}) [{}] {}",
+                        read_marker,
+                        pinned_marker,
+                        activity_marker,
+                        index + 1,
+                        n.kind.to_lowercase(),
+                        n.subject
+                    ),
                     Style::default()
                         .add_modifier(Modifier::BOLD)
                         .fg(Color::Rgb(34, 139, 34)), // Dark Green,
                 )),
-                Spans::from(format!("{} ⏰ {} ago", n.name, parse_into_duration(&n.updated_at))),
+                match &n.snoozed_until {
+                    Some(snoozed_until) if app.view == View::Snoozed => Spans::from(format!(
+                        "{} 💤 until {}",
+                        n.name,
+                        DateTime::parse_from_rfc3339(snoozed_until)
+                            .map(|until| until.with_timezone(&chrono::Local).format("%a %d %b %H:%M").to_string())
+                            .unwrap_or_else(|_| snoozed_until.clone())
+                    )),
+                    _ => Spans::from(format!("{} ⏰ {} ago", n.name, parse_into_duration(&n.updated_at))),
+                },
             ];
+            if let Some(annotation) = app.annotations.get(&n.id) {
+                let note_marker = if annotation.note.is_some() { "📝 " } else { "" };
+                let tags: Vec<String> = annotation.tags.iter().map(|tag| format!("#{}", tag)).collect();
+                lines.push(Spans::from(Span::styled(
+                    format!("{}{}", note_marker, tags.join(" ")),
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
             ListItem::new(lines).style(Style::default().fg(Color::White))
         })
         .collect();
 
     let items = List::new(items)
         .block(Block::default().borders(Borders::ALL).title(Span::styled(
-            format!(" ghostie - Showing {} notifications ", total_notifications),
+            format!(
+                " ghostie - Showing {} {}notifications {}{}{}",
+                total_notifications,
+                view_name,
+                filter_status,
+                pinned_status,
+                pending_actions_status(&app.pending_actions)
+            ),
             Style::default().add_modifier(Modifier::BOLD),
         )))
         .highlight_style(Style::default().bg(Color::DarkGray))
         .highlight_symbol("👉");
 
-    f.render_stateful_widget(items, chunks[0], &mut app.items.state);
-    f.render_widget(help_block(), chunks[1]);
+    f.render_stateful_widget(items, panes[0], &mut app.items.state);
+    f.render_widget(preview(app), panes[1]);
+    f.render_widget(help_block(app), chunks[1]);
 }